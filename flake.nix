@@ -131,7 +131,7 @@
             (craneLib.filterCargoSources path type)
             || (builtins.match ".*assets/.*" path != null)
             || (builtins.match ".*Dioxus.toml" path != null)
-            || (builtins.match ".*diesel.toml" path != null);
+            || (builtins.match ".*migrations/.*\\.sql" path != null);
         # Only keep Cargo.toml and Cargo.lock, for building dependencies
         manifestFilter = path: type:
             (craneLib.filterCargoSources path type)
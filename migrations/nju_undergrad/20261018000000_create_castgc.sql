@@ -0,0 +1,6 @@
+-- CASTGC cookies of NJU authserver, shared by all NJU adapters
+CREATE TABLE IF NOT EXISTS castgc (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    last_access TEXT NOT NULL
+);
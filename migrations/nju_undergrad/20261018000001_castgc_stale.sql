@@ -0,0 +1,2 @@
+-- Set when the school no longer accepts the cookie
+ALTER TABLE castgc ADD COLUMN stale INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Rows written before encryption have 0 here, and are encrypted at startup
+ALTER TABLE castgc ADD COLUMN encrypted INTEGER NOT NULL DEFAULT 0;
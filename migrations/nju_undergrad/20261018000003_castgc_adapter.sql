@@ -0,0 +1,3 @@
+-- Which adapter the credential belongs to.
+-- NULL for rows saved before we record it, claimed by the first adapter using it.
+ALTER TABLE castgc ADD COLUMN adapter TEXT;
@@ -0,0 +1,4 @@
+-- When the school stopped accepting the cookie, so that we can delete it some time later.
+-- Calendar apps keep polling and updating last_access even after that.
+ALTER TABLE castgc ADD COLUMN stale_since TEXT;
+UPDATE castgc SET stale_since = last_access WHERE stale = 1;
@@ -0,0 +1,7 @@
+-- SEQUENCE and LAST-MODIFIED of each calendar event
+CREATE TABLE IF NOT EXISTS event_revision (
+    uid TEXT PRIMARY KEY,
+    fingerprint TEXT NOT NULL,
+    sequence INTEGER NOT NULL,
+    last_modified TEXT NOT NULL
+);
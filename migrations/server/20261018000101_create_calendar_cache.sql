@@ -0,0 +1,11 @@
+-- Generated calendars, keyed by adapter and hashed subscription key
+CREATE TABLE IF NOT EXISTS calendar_cache (
+    adapter TEXT NOT NULL,
+    key TEXT NOT NULL,
+    courses TEXT NOT NULL,
+    ics BLOB NOT NULL,
+    etag TEXT NOT NULL,
+    last_modified TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    PRIMARY KEY (adapter, key)
+);
@@ -0,0 +1,14 @@
+-- Subscriptions with different options get different calendars.
+-- It's only a cache, so just start over.
+DROP TABLE calendar_cache;
+CREATE TABLE calendar_cache (
+    adapter TEXT NOT NULL,
+    key TEXT NOT NULL,
+    options TEXT NOT NULL,
+    courses TEXT NOT NULL,
+    ics BLOB NOT NULL,
+    etag TEXT NOT NULL,
+    last_modified TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    PRIMARY KEY (adapter, key, options)
+);
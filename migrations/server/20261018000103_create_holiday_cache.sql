@@ -0,0 +1,6 @@
+-- Last holidays got from each source, used when the source is unreachable
+CREATE TABLE IF NOT EXISTS holiday_cache (
+    source TEXT PRIMARY KEY,
+    holidays TEXT NOT NULL,
+    fetched_at TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- The cache now stores the semester along with courses.
+-- It's only a cache, so just start over.
+DELETE FROM calendar_cache;
+ALTER TABLE calendar_cache RENAME COLUMN courses TO timetable;
@@ -0,0 +1,15 @@
+-- Revisions are kept per subscription, since subscribers with different options get
+-- different events under the same UID. Old rows can't tell whose they are, so start over.
+DROP TABLE event_revision;
+CREATE TABLE event_revision (
+    adapter TEXT NOT NULL,
+    key TEXT NOT NULL,
+    variant TEXT NOT NULL,
+    uid TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    sequence INTEGER NOT NULL,
+    last_modified TEXT NOT NULL,
+    -- When the calendar with this event was last generated, for purging
+    last_seen TEXT NOT NULL,
+    PRIMARY KEY (adapter, key, variant, uid)
+);
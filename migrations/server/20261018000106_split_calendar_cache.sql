@@ -0,0 +1,23 @@
+-- The timetable is fetched once per subscription and shared by all its variants
+-- (options, client app and file format), which only cache the files generated from it.
+-- It's only a cache, so just start over.
+DROP TABLE calendar_cache;
+CREATE TABLE timetable_cache (
+    adapter TEXT NOT NULL,
+    key TEXT NOT NULL,
+    timetable TEXT NOT NULL,
+    fetched_at TEXT NOT NULL,
+    PRIMARY KEY (adapter, key)
+);
+CREATE TABLE calendar_cache (
+    adapter TEXT NOT NULL,
+    key TEXT NOT NULL,
+    options TEXT NOT NULL,
+    ics BLOB NOT NULL,
+    etag TEXT NOT NULL,
+    last_modified TEXT NOT NULL,
+    -- fetched_at of the timetable this is generated from
+    fetched_at TEXT NOT NULL,
+    rendered_at TEXT NOT NULL,
+    PRIMARY KEY (adapter, key, options)
+);
@@ -0,0 +1,204 @@
+//! Where course locations are, shared by adapters.
+//!
+//! Buildings are data: those bundled from `buildings.toml`, plus those the deployer
+//! adds in a file of the same format (see `buildings_path` in `config.toml`).
+
+use super::geo::GeoLocation;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::path::Path;
+use tracing::instrument;
+
+/// A building that courses take place in.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Building {
+    /// e.g. `仙Ⅱ`
+    pub name: String,
+    /// Other names locations may use, e.g. `仙2`
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Prefixes of location IDs in this building, e.g. `S01` for `S01B207`
+    #[serde(default)]
+    pub codes: Vec<String>,
+    /// e.g. `仙林`
+    pub campus: String,
+    #[serde(flatten)]
+    pub geo: GeoLocation,
+}
+
+/// A campus, whose coordinates are used when no building matches.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Campus {
+    /// e.g. `仙林`, which the campus names from school (`仙林校区`) contain
+    pub name: String,
+    #[serde(flatten)]
+    pub geo: GeoLocation,
+}
+
+/// Format of `buildings.toml`, and building files of deployers in JSON or TOML.
+#[derive(Debug, Default, Deserialize)]
+struct BuildingFile {
+    #[serde(default)]
+    campuses: Vec<Campus>,
+    #[serde(default)]
+    buildings: Vec<Building>,
+}
+
+/// Resolves course locations to buildings and coordinates.
+#[derive(Debug, Default)]
+pub struct BuildingRegistry {
+    campuses: Vec<Campus>,
+    buildings: Vec<Building>,
+}
+
+impl BuildingRegistry {
+    /// Buildings bundled into the binary.
+    pub fn bundled() -> Result<Self> {
+        let file: BuildingFile = toml::from_str(include_str!("buildings.toml"))
+            .context("Failed to parse bundled buildings")?;
+        Ok(Self {
+            campuses: file.campuses,
+            buildings: file.buildings,
+        })
+    }
+
+    /// Bundled buildings, plus those in `path` if given.
+    pub fn new(path: Option<&str>) -> Result<Self> {
+        let mut registry = Self::bundled()?;
+        if let Some(path) = path {
+            registry.extend_from_file(Path::new(path))?;
+        }
+        Ok(registry)
+    }
+
+    /// Add buildings from a JSON or TOML file, decided by extension.
+    ///
+    /// They win over bundled ones that match equally well, so deployers can correct them.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read buildings from {}", path.display()))?;
+
+        let file: BuildingFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            Some("toml") => toml::from_str(&content)?,
+            _ => bail!(
+                "Unknown format of building file {}, expected .json or .toml",
+                path.display()
+            ),
+        };
+        self.campuses.extend(file.campuses);
+        self.buildings.extend(file.buildings);
+        Ok(())
+    }
+
+    /// The building `location` is in.
+    ///
+    /// `location` is matched by building names and aliases, and `location_id` by codes.
+    /// The longest match wins. Buildings on other campuses are ignored if `campus` is known.
+    #[instrument(skip(self), ret)]
+    pub fn building(
+        &self,
+        location: Option<&str>,
+        location_id: Option<&str>,
+        campus: Option<&str>,
+    ) -> Option<&Building> {
+        let campus = campus.filter(|campus| !campus.is_empty());
+
+        let mut best: Option<(usize, &Building)> = None;
+        for building in &self.buildings {
+            if campus.is_some_and(|campus| !campus.contains(&building.campus)) {
+                continue;
+            }
+
+            let by_name = location.into_iter().flat_map(|location| {
+                std::iter::once(&building.name)
+                    .chain(&building.aliases)
+                    .filter(move |name| location.contains(name.as_str()))
+            });
+            let by_code = location_id.into_iter().flat_map(|location_id| {
+                building
+                    .codes
+                    .iter()
+                    .filter(move |code| location_id.starts_with(code.as_str()))
+            });
+            let Some(matched) = by_name.chain(by_code).map(|key| key.chars().count()).max() else {
+                continue;
+            };
+
+            // Later ones win ties, so that deployers' buildings override bundled ones
+            if best.is_none_or(|(longest, _)| matched >= longest) {
+                best = Some((matched, building));
+            }
+        }
+
+        best.map(|(_, building)| building)
+    }
+
+    /// The campus named `campus`, e.g. `仙林校区`.
+    pub fn campus(&self, campus: &str) -> Option<&Campus> {
+        self.campuses
+            .iter()
+            .rev()
+            .find(|known| campus.contains(&known.name))
+    }
+
+    /// Coordinates of `location`: its building, or its campus if no building matches.
+    pub fn locate(
+        &self,
+        location: Option<&str>,
+        location_id: Option<&str>,
+        campus: Option<&str>,
+    ) -> Option<GeoLocation> {
+        self.building(location, location_id, campus)
+            .map(|building| building.geo)
+            .or_else(|| campus.and_then(|campus| self.campus(campus)).map(|c| c.geo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(building: Option<&Building>) -> Option<&str> {
+        building.map(|building| building.name.as_str())
+    }
+
+    #[test]
+    fn bundled_buildings() {
+        let registry = BuildingRegistry::bundled().unwrap();
+
+        assert_eq!(
+            name(registry.building(Some("仙Ⅱ-212"), None, None)),
+            Some("仙Ⅱ")
+        );
+        assert_eq!(
+            name(registry.building(Some("仙2-212"), None, None)),
+            Some("仙Ⅱ")
+        );
+        assert_eq!(
+            name(registry.building(None, Some("S01B207"), Some("苏州校区"))),
+            Some("苏教")
+        );
+        // Only the campus is known
+        assert_eq!(name(registry.building(Some("教学楼101"), None, None)), None);
+        assert!(
+            registry
+                .locate(Some("教学楼101"), None, Some("鼓楼校区"))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn other_campuses_are_ignored() {
+        let registry = BuildingRegistry::bundled().unwrap();
+
+        assert_eq!(
+            name(registry.building(Some("逸夫管理科学楼101"), None, None)),
+            Some("逸夫管理科学楼")
+        );
+        assert_eq!(
+            name(registry.building(Some("逸B-101"), None, Some("鼓楼校区"))),
+            None
+        );
+    }
+}
@@ -1,22 +1,42 @@
 //! Defines the [`Course`] struct and how it converts to an iCalendar file.
 
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 use ics::{
     Event,
     components::{Parameter, Property},
-    parameters::TzIDParam,
-    properties::{Description, DtEnd, DtStart, Geo, Location, Summary},
+    parameters::{TzIDParam, Value},
+    properties::{
+        Categories, Color, Description, DtEnd, DtStart, ExDate, Geo, LastModified, Location, RRule,
+        Sequence, Summary, Transp,
+    },
 };
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::adapters::geo::GeoLocation;
+use crate::adapters::recurrence::Recurrence;
+use crate::adapters::timezone::SchoolTimeZone;
 use crate::adapters::traits::School;
+use crate::server::options::{CalendarOptions, NotesLevel};
+use crate::server::revisions::Revision;
 
 /// A course
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Course {
     /// Course name
     pub name: String,
+    /// Identifies this course within its semester, e.g. the class name and slot
+    /// `形势与政策16班/周2/5-6节` (see [`slot_id`]).
+    ///
+    /// This is used to derive event UIDs, so it must stay the same across fetches.
+    pub id: String,
+    /// The semester this course belongs to, e.g. `2025-2026-1`.
+    pub semester: String,
+    /// What kind of event this is.
+    #[serde(default)]
+    pub kind: CourseKind,
     /// All times of course, including each one across the semester.
     /// Format is `Vec<(start_time, end_time)>`.
     pub time: Vec<(DateTime<Utc>, DateTime<Utc>)>,
@@ -26,72 +46,325 @@ pub struct Course {
     pub geo: Option<GeoLocation>,
     /// The campus of this course
     pub campus: Option<String>,
-    /// Additional notes.
+    /// ID of the location, e.g. `S01B207`
+    #[serde(default)]
+    pub location_id: Option<String>,
+    /// Course code, e.g. `081200B71`
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Class name, e.g. `形势与政策16班`
+    #[serde(default)]
+    pub class_name: Option<String>,
+    /// Names of teachers
+    #[serde(default)]
+    pub teachers: Vec<String>,
+    /// Credits
+    #[serde(default)]
+    pub credits: Option<f32>,
+    /// Classes of students attending this course, e.g. `2022计算机学院计算机科学与技术`
+    #[serde(default)]
+    pub attending_classes: Vec<String>,
+    /// Remarks from course selection (选课备注)
+    #[serde(default)]
+    pub remarks: Option<String>,
+    /// Teaching weeks (1-based) this course happens in
+    #[serde(default)]
+    pub weeks: Vec<u32>,
+    /// First and last period (节次, 1-based), e.g. `(5, 6)`
+    #[serde(default)]
+    pub periods: Option<(u32, u32)>,
+    /// Whether this is an all-day event, like `第1周`.
     ///
-    /// This would be in the notes area of calendar event, and you can
-    /// include anything like notes, teacher, notice or whatsoever.
+    /// Times are then midnights in the school's time zone, and events are written as dates.
+    #[serde(default)]
+    pub all_day: bool,
+    /// Additional notes that don't fit in other fields.
     ///
-    /// When displayed, the vec of string will be concatenated with
-    /// new lines (each one in its own line)
+    /// This would be in the notes area of calendar event, after what we render
+    /// from other fields. Each string is in its own line.
     pub notes: Vec<String>,
 }
 
-/// Location of a course
-#[derive(Debug, Clone, Copy)]
-pub struct GeoLocation {
-    latitude: f64,
-    longitude: f64,
+/// Teaching weeks (1-based) from a bitmap like `001000100010001000000000000000`.
+pub fn weeks_from_bitmap(bitmap: &str) -> Vec<u32> {
+    bitmap
+        .chars()
+        .zip(1..)
+        .filter(|(have_course, _)| *have_course == '1')
+        .map(|(_, week)| week)
+        .collect()
 }
 
-impl GeoLocation {
-    pub fn new(latitude: f64, longitude: f64) -> Self {
-        Self {
-            latitude,
-            longitude,
+/// ID of a class in one of its weekly slots, e.g. `形势与政策16班/周2/5-6节`.
+///
+/// Schools list a class that meets more than once a week as a row per slot, which become
+/// separate courses, so the slot tells them apart.
+pub fn slot_id(class: &str, weekday: i32, first_period: i32, last_period: i32) -> String {
+    format!("{class}/周{weekday}/{first_period}-{last_period}节")
+}
+
+/// Split a list of names like `王可,李四`.
+pub fn split_names(names: &str) -> Vec<String> {
+    names
+        .split([',', '，'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// What kind of event a [`Course`] is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CourseKind {
+    /// A regular class
+    #[default]
+    Lecture,
+    /// A lab session.
+    ///
+    /// The NJU adapters don't produce this, as their APIs don't tell labs apart from
+    /// other classes.
+    Lab,
+    /// An exam
+    Exam,
+    /// A make-up class for one cancelled before
+    Makeup,
+    /// Anything else, with a name to show as its category
+    Custom(String),
+}
+
+impl CourseKind {
+    /// Names used in subscription options, e.g. `?alarm.exam=1d`.
+    pub const NAMES: [&str; 5] = ["lecture", "lab", "exam", "makeup", "custom"];
+
+    /// The name used in subscription options, one of [`CourseKind::NAMES`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            CourseKind::Lecture => "lecture",
+            CourseKind::Lab => "lab",
+            CourseKind::Exam => "exam",
+            CourseKind::Makeup => "makeup",
+            CourseKind::Custom(_) => "custom",
         }
     }
 
-    pub fn to_ical_str(&self) -> String {
-        format!("{};{}", self.latitude, self.longitude)
+    /// Shown as `CATEGORIES` of events.
+    pub fn category(&self) -> &str {
+        match self {
+            CourseKind::Lecture => "课程",
+            CourseKind::Lab => "实验",
+            CourseKind::Exam => "考试",
+            CourseKind::Makeup => "补课",
+            CourseKind::Custom(name) => name,
+        }
     }
 
-    pub fn to_apple_location_str(&self) -> String {
-        format!("geo:{},{}", self.latitude, self.longitude)
+    /// A CSS color name for `COLOR` of events (RFC 7986).
+    pub fn color(&self) -> &'static str {
+        match self {
+            CourseKind::Lecture => "steelblue",
+            CourseKind::Lab => "seagreen",
+            CourseKind::Exam => "crimson",
+            CourseKind::Makeup => "darkorange",
+            CourseKind::Custom(_) => "gray",
+        }
     }
 
-    pub fn to_coloros_location_str(&self) -> String {
-        format!(
-            r#"{{"EVENT_ADDRESS":"{{\"lat\":{},\"lon\":{}}}"}}"#,
-            self.latitude, self.longitude
-        )
+    /// Whether the student is busy during such events. Custom events are
+    /// informational, so they don't block time.
+    pub fn is_busy(&self) -> bool {
+        !matches!(self, CourseKind::Custom(_))
     }
 }
 
 const TIME_FMT: &str = "%Y%m%dT%H%M%S";
+const UTC_TIME_FMT: &str = "%Y%m%dT%H%M%SZ";
 impl Course {
-    pub fn to_events<'a>(&self, school: &dyn School, tzid: &str) -> Result<Vec<Event<'a>>> {
+    /// An all-day event on `days`, from the first to the last day, both inclusive.
+    pub fn all_day(
+        semester: &str,
+        id: String,
+        name: String,
+        kind: CourseKind,
+        days: (NaiveDate, NaiveDate),
+        timezone: &SchoolTimeZone,
+    ) -> Self {
+        let midnight = |date: NaiveDate| {
+            date.and_time(NaiveTime::MIN)
+                .and_local_timezone(timezone.offset())
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        Self {
+            name,
+            id,
+            semester: semester.to_string(),
+            kind,
+            time: vec![(midnight(days.0), midnight(days.1 + TimeDelta::days(1)))],
+            location: None,
+            geo: None,
+            campus: None,
+            location_id: None,
+            code: None,
+            class_name: None,
+            teachers: vec![],
+            credits: None,
+            attending_classes: vec![],
+            remarks: None,
+            weeks: vec![],
+            periods: None,
+            all_day: true,
+            notes: vec![],
+        }
+    }
+
+    /// Lines in the notes area of calendar events.
+    pub fn description_lines(&self, level: NotesLevel) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(campus) = &self.campus {
+            lines.push(campus.clone());
+        }
+        if level == NotesLevel::Minimal {
+            // Plugins add notes the subscriber asked for, like week numbers, or that they
+            // shouldn't miss, like holiday changes
+            lines.extend(self.notes.iter().cloned());
+            return lines;
+        }
+
+        if let Some(class_name) = &self.class_name {
+            lines.push(format!("班级：{class_name}"));
+        }
+        if !self.teachers.is_empty() {
+            lines.push(format!("教师：{}", self.teachers.join("、")));
+        }
+        if !self.attending_classes.is_empty() {
+            lines.push(format!("上课班级：{}", self.attending_classes.join("，")));
+        }
+        if let Some(credits) = self.credits {
+            lines.push(format!("学分：{credits}"));
+        }
+        if let Some(remarks) = &self.remarks {
+            lines.push(format!("选课备注：{remarks}"));
+        }
+        lines.extend(self.notes.iter().cloned());
+
+        lines
+    }
+
+    /// Occurrences of this course, grouped into weekly series, each with a key that
+    /// identifies it within the course, see [`Recurrence::key`].
+    pub fn recurrences(&self) -> Vec<(String, Recurrence)> {
+        Recurrence::from_times(&self.time)
+            .into_iter()
+            .map(|recurrence| (recurrence.key(), recurrence))
+            .collect()
+    }
+
+    /// The UID of the event for the series with `series_key`, see [`Course::recurrences`].
+    ///
+    /// It is derived from the adapter, semester, course ID and the slot of the series, so
+    /// refreshing the calendar gives the same UIDs and calendar apps keep the alarms and
+    /// notes attached to events. When occurrences of a series change, e.g. weeks are
+    /// cancelled or the room changes, it keeps its UID and gets a new `SEQUENCE`.
+    ///
+    /// `adapter`: name of the school adapter, see [`School::adapter_name`].
+    pub fn event_uid(&self, adapter: &str, series_key: &str) -> String {
+        let seed = format!("{}/{}/{}/{}", adapter, self.semester, self.id, series_key);
+
+        Uuid::new_v5(&Uuid::NAMESPACE_OID, seed.as_bytes()).to_string()
+    }
+
+    /// Everything about an event that, when changed, means it has moved.
+    ///
+    /// See [`crate::server::revisions`].
+    pub fn event_fingerprint(&self, recurrence: &Recurrence) -> String {
+        let exdates: Vec<_> = recurrence
+            .exdates
+            .iter()
+            .map(|exdate| exdate.format(TIME_FMT).to_string())
+            .collect();
+
+        format!(
+            "{}/{}/{}/{}/{}",
+            recurrence.start.format(TIME_FMT),
+            recurrence.end().format(TIME_FMT),
+            recurrence.rrule(),
+            exdates.join(","),
+            self.location.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// All `(uid, fingerprint)` pairs of this course.
+    pub fn event_fingerprints(&self, school: &dyn School) -> Vec<(String, String)> {
+        self.recurrences()
+            .iter()
+            .map(|(series_key, recurrence)| {
+                (
+                    self.event_uid(school.adapter_name(), series_key),
+                    self.event_fingerprint(recurrence),
+                )
+            })
+            .collect()
+    }
+
+    pub fn to_events<'a>(
+        &self,
+        school: &dyn School,
+        revisions: &HashMap<String, Revision>,
+        options: &CalendarOptions,
+    ) -> Result<Vec<Event<'a>>> {
+        let tz = school.timezone();
+        let dialect = options.dialect();
         Ok(self
-            .time
+            .recurrences()
             .iter()
-            .map(|time| {
-                let mut event = Event::new(
-                    Uuid::new_v4().to_string(),
-                    chrono::Utc::now().format(TIME_FMT).to_string(),
-                );
+            .map(|(series_key, recurrence)| {
+                let uid = self.event_uid(school.adapter_name(), series_key);
+                let revision = revisions
+                    .get(&uid)
+                    .cloned()
+                    .unwrap_or_else(Revision::initial);
+                let last_modified = revision.last_modified.format(UTC_TIME_FMT).to_string();
+
+                let mut event = Event::new(uid, last_modified.clone());
+                event.push(LastModified::new(last_modified));
+                event.push(Sequence::new(revision.sequence.to_string()));
 
                 // Name
-                event.push(Summary::new(self.name.clone()));
+                event.push(Summary::new(dialect.escape(&self.name)));
+
+                // Kind
+                event.push(Categories::new(self.kind.category().to_string()));
+                event.push(Color::new(self.kind.color()));
+                event.push(if self.kind.is_busy() {
+                    Transp::opaque()
+                } else {
+                    Transp::transparent()
+                });
+                if dialect.outlook_busy_status() {
+                    event.push(Property::new(
+                        "X-MICROSOFT-CDO-BUSYSTATUS",
+                        if self.kind.is_busy() { "BUSY" } else { "FREE" },
+                    ));
+                }
 
                 // Location
                 if let Some(location) = self.location.clone() {
-                    event.push(Location::new(format!(
-                        "{}\\n{}",
+                    event.push(Location::new(dialect.escape(&format!(
+                        "{}\n{}",
                         location,
                         school.school_name()
-                    )));
-                    if let Some(geo) = self.geo {
-                        // Apple calendar
+                    ))));
+                    if let Some(geo) = self.geo
+                        && options.geo
+                    {
                         event.push(Geo::new(geo.to_ical_str()));
+                    }
+                    if let Some(geo) = self.geo
+                        && options.geo
+                        && dialect.apple_location()
+                    {
+                        // Apple calendar
                         let mut apple_addr = Property::new(
                             "X-APPLE-STRUCTURED-LOCATION",
                             geo.to_apple_location_str(),
@@ -102,7 +375,11 @@ impl Course {
                         ));
                         apple_addr.add(Parameter::new("X-TITLE", location.clone()));
                         event.push(apple_addr);
-
+                    }
+                    if let Some(geo) = self.geo
+                        && options.geo
+                        && dialect.coloros_location()
+                    {
                         // ColorOS 16 calendar
                         // Importing it via iCSx5 won't work, and importing via native calendar always reports network error.
                         // But manually importing with this HAS map, so I'll just leave it here.
@@ -113,27 +390,116 @@ impl Course {
                 }
 
                 // Notes
-                let mut notes = "".to_string();
-                if let Some(campus) = &self.campus {
-                    notes += format!("{}\n", campus).as_str();
-                }
-                for note in &self.notes {
-                    notes += format!("{}\n", note).as_str();
+                let mut notes = self.description_lines(options.notes);
+                if let (Some(location), Some(geo)) = (&self.location, self.geo)
+                    && options.geo
+                    && options.notes == NotesLevel::Full
+                {
+                    notes.extend(geo.map_links(location));
                 }
-                event.push(Description::new(notes.replace("\n", "\\n")));
+                event.push(Description::new(dialect.escape(&notes.join("\n"))));
 
-                let timezone = TzIDParam::new(tzid.to_string());
+                // All-day events are written as dates, others as local time
+                let (format_time, time_param): (
+                    fn(&SchoolTimeZone, &DateTime<Utc>) -> String,
+                    Parameter,
+                ) = if self.all_day {
+                    (SchoolTimeZone::format_local_date, Value::DATE.into())
+                } else {
+                    (SchoolTimeZone::format_local, TzIDParam::new(tz.tzid).into())
+                };
 
-                let mut start = DtStart::new(time.0.format(TIME_FMT).to_string());
-                start.add(timezone.clone());
+                let mut start = DtStart::new(format_time(&tz, &recurrence.start));
+                start.add(time_param.clone());
                 event.push(start);
 
-                let mut end = DtEnd::new(time.1.format(TIME_FMT).to_string());
-                end.add(timezone.clone());
+                let mut end = DtEnd::new(format_time(&tz, &recurrence.end()));
+                end.add(time_param.clone());
                 event.push(end);
 
+                // Recurrence
+                if recurrence.is_recurring() {
+                    event.push(RRule::new(if self.all_day {
+                        recurrence.date_rrule(&tz.format_local_date(&recurrence.until))
+                    } else {
+                        recurrence.rrule()
+                    }));
+                }
+                if !recurrence.exdates.is_empty() {
+                    let exdates: Vec<_> = recurrence
+                        .exdates
+                        .iter()
+                        .map(|exdate| format_time(&tz, exdate))
+                        .collect();
+                    let mut exdate = ExDate::new(exdates.join(","));
+                    exdate.add(time_param.clone());
+                    event.push(exdate);
+                }
+
                 event
             })
             .collect())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    /// A class at 08:00-09:50 UTC+8 in each of `weeks` of a semester starting on Monday
+    /// 2025-09-01, `days` after Monday.
+    pub(crate) fn class(id: &str, weeks: impl IntoIterator<Item = i64>, days: i64) -> Course {
+        let monday = Utc.with_ymd_and_hms(2025, 9, 1, 0, 0, 0).unwrap();
+        Course {
+            name: "高等数学".to_string(),
+            id: id.to_string(),
+            semester: "2025-2026-1".to_string(),
+            kind: CourseKind::Lecture,
+            time: weeks
+                .into_iter()
+                .map(|week| {
+                    let start = monday + Duration::weeks(week - 1) + Duration::days(days);
+                    (start, start + Duration::minutes(110))
+                })
+                .collect(),
+            location: Some("仙Ⅱ-207".to_string()),
+            geo: None,
+            campus: None,
+            location_id: None,
+            code: None,
+            class_name: None,
+            teachers: vec![],
+            credits: None,
+            attending_classes: vec![],
+            remarks: None,
+            weeks: vec![],
+            periods: None,
+            all_day: false,
+            notes: vec![],
+        }
+    }
+
+    fn uids(course: &Course) -> Vec<String> {
+        let mut uids: Vec<_> = course
+            .recurrences()
+            .iter()
+            .map(|(key, _)| course.event_uid("南京大学本科生", key))
+            .collect();
+        uids.sort();
+        uids
+    }
+
+    #[test]
+    fn uids_stay_when_first_occurrence_is_removed() {
+        // Wednesday from week 1 and Monday from week 2
+        let mut course = class("高等数学01班", 1..=16, 2);
+        course.time.extend(class("", 2..=16, 0).time);
+        let before = uids(&course);
+        assert_eq!(before.len(), 2);
+
+        // A holiday removes the first Wednesday
+        course.time.remove(0);
+        assert_eq!(uids(&course), before);
+    }
+}
@@ -0,0 +1,107 @@
+//! Protects login credentials stored in database.
+//!
+//! A credential is only ever needed when someone requests the calendar with its
+//! subscription key, so we derive everything from that key:
+//! - The database row is looked up by a hash of the key, so the key itself is never stored.
+//! - The credential is encrypted with AES-256-GCM, using another hash of the key.
+//!
+//! This way, the database file alone is useless to whoever steals it.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Digest, Sha256};
+
+/// Length of AES-GCM nonce, in bytes
+const NONCE_LEN: usize = 12;
+
+/// The value to store in database instead of the subscription key.
+pub fn lookup_key(subscription_key: &str) -> String {
+    format!(
+        "{:x}",
+        Sha256::digest(format!("nju-schedule-ics/lookup/{subscription_key}"))
+    )
+}
+
+/// The AES key to encrypt the credential of `subscription_key` with.
+fn encryption_key(subscription_key: &str) -> Key<Aes256Gcm> {
+    let key = Sha256::digest(format!("nju-schedule-ics/encrypt/{subscription_key}"));
+    *Key::<Aes256Gcm>::from_slice(&key)
+}
+
+fn cipher(subscription_key: &str) -> Aes256Gcm {
+    Aes256Gcm::new(&encryption_key(subscription_key))
+}
+
+/// Encrypt `plaintext` with the subscription key.
+///
+/// The result is base64 encoded, with the nonce in front.
+pub fn seal(subscription_key: &str, plaintext: &str) -> Result<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(subscription_key)
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow!("Failed to encrypt credential"))?;
+
+    Ok(general_purpose::STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+/// Decrypt what [`seal`] produced.
+pub fn open(subscription_key: &str, sealed: &str) -> Result<String> {
+    let sealed = general_purpose::STANDARD.decode(sealed)?;
+    if sealed.len() < NONCE_LEN {
+        return Err(anyhow!("Encrypted credential too short"));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let plaintext = cipher(subscription_key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt credential"))?;
+
+    Ok(String::from_utf8(plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "3f1a9c2e-0b6d-4e8f-a1c5-7d2e9b4f6a80";
+    const COOKIE: &str = "TGT-1234-abcdefghijklmnopqrstuvwxyz-cas";
+
+    #[test]
+    fn round_trip() {
+        let sealed = seal(KEY, COOKIE).unwrap();
+        assert!(!sealed.contains(COOKIE));
+        assert_eq!(open(KEY, &sealed).unwrap(), COOKIE);
+
+        // Nonces are random
+        assert_ne!(seal(KEY, COOKIE).unwrap(), sealed);
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let sealed = seal(KEY, COOKIE).unwrap();
+        assert!(open("another-key", &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails() {
+        let mut sealed = general_purpose::STANDARD
+            .decode(seal(KEY, COOKIE).unwrap())
+            .unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        let tampered = general_purpose::STANDARD.encode(&sealed);
+
+        assert!(open(KEY, &tampered).is_err());
+        assert!(open(KEY, "c2hvcnQ=").is_err());
+        assert!(open(KEY, "not base64!").is_err());
+    }
+
+    #[test]
+    fn lookup_key_is_not_encryption_key() {
+        let lookup = lookup_key(KEY);
+        assert_ne!(lookup, format!("{:x}", encryption_key(KEY)));
+        assert_ne!(lookup, KEY);
+        assert_eq!(lookup, lookup_key(KEY));
+    }
+}
@@ -0,0 +1,247 @@
+//! Coordinates of course locations, and the coordinate systems (datums) used in China.
+//!
+//! Maps in China don't use WGS-84 as GPS does. Amap, Tencent Maps and Apple Maps in
+//! China use GCJ-02, which is WGS-84 with an obfuscating offset of a few hundred meters,
+//! and Baidu Maps uses BD-09, which is GCJ-02 with another offset. So each consumer
+//! needs coordinates in its own datum.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use urlencoding::encode as url_encode;
+
+/// A coordinate system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Datum {
+    /// GPS, Google Maps outside China, and what `GEO` in iCalendar means
+    Wgs84,
+    /// Amap, Tencent Maps and Apple Maps in China
+    #[default]
+    Gcj02,
+    /// Baidu Maps
+    Bd09,
+}
+
+/// Location of a course
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GeoLocation {
+    latitude: f64,
+    longitude: f64,
+    /// Locations cached before datums were recorded were copied from Apple Maps, so GCJ-02
+    #[serde(default)]
+    datum: Datum,
+}
+
+/// Semi-major axis of Krasovsky 1940, used by GCJ-02
+const A: f64 = 6378245.0;
+/// Eccentricity squared of Krasovsky 1940
+const EE: f64 = 0.006_693_421_622_965_943;
+/// Used by BD-09
+const X_PI: f64 = PI * 3000.0 / 180.0;
+
+fn out_of_china(latitude: f64, longitude: f64) -> bool {
+    !(72.004..=137.8347).contains(&longitude) || !(0.8293..=55.8271).contains(&latitude)
+}
+
+fn offset_latitude(x: f64, y: f64) -> f64 {
+    let mut result = -100.0 + 2.0 * x + 3.0 * y + 0.2 * y * y + 0.1 * x * y + 0.2 * x.abs().sqrt();
+    result += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    result += (20.0 * (y * PI).sin() + 40.0 * (y / 3.0 * PI).sin()) * 2.0 / 3.0;
+    result += (160.0 * (y / 12.0 * PI).sin() + 320.0 * (y * PI / 30.0).sin()) * 2.0 / 3.0;
+    result
+}
+
+fn offset_longitude(x: f64, y: f64) -> f64 {
+    let mut result = 300.0 + x + 2.0 * y + 0.1 * x * x + 0.1 * x * y + 0.1 * x.abs().sqrt();
+    result += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    result += (20.0 * (x * PI).sin() + 40.0 * (x / 3.0 * PI).sin()) * 2.0 / 3.0;
+    result += (150.0 * (x / 12.0 * PI).sin() + 300.0 * (x / 30.0 * PI).sin()) * 2.0 / 3.0;
+    result
+}
+
+fn wgs84_to_gcj02(latitude: f64, longitude: f64) -> (f64, f64) {
+    if out_of_china(latitude, longitude) {
+        return (latitude, longitude);
+    }
+
+    let d_latitude = offset_latitude(longitude - 105.0, latitude - 35.0);
+    let d_longitude = offset_longitude(longitude - 105.0, latitude - 35.0);
+    let rad_latitude = latitude / 180.0 * PI;
+    let magic = 1.0 - EE * rad_latitude.sin().powi(2);
+    let sqrt_magic = magic.sqrt();
+
+    (
+        latitude + d_latitude * 180.0 / ((A * (1.0 - EE)) / (magic * sqrt_magic) * PI),
+        longitude + d_longitude * 180.0 / (A / sqrt_magic * rad_latitude.cos() * PI),
+    )
+}
+
+/// There's no closed form, so iterate until the error is well below a meter.
+fn gcj02_to_wgs84(latitude: f64, longitude: f64) -> (f64, f64) {
+    let (mut wgs_latitude, mut wgs_longitude) = (latitude, longitude);
+    for _ in 0..10 {
+        let (gcj_latitude, gcj_longitude) = wgs84_to_gcj02(wgs_latitude, wgs_longitude);
+        let (d_latitude, d_longitude) = (gcj_latitude - latitude, gcj_longitude - longitude);
+        wgs_latitude -= d_latitude;
+        wgs_longitude -= d_longitude;
+        if d_latitude.abs() < 1e-7 && d_longitude.abs() < 1e-7 {
+            break;
+        }
+    }
+    (wgs_latitude, wgs_longitude)
+}
+
+fn gcj02_to_bd09(latitude: f64, longitude: f64) -> (f64, f64) {
+    let z =
+        (longitude * longitude + latitude * latitude).sqrt() + 0.00002 * (latitude * X_PI).sin();
+    let theta = latitude.atan2(longitude) + 0.000003 * (longitude * X_PI).cos();
+    (z * theta.sin() + 0.006, z * theta.cos() + 0.0065)
+}
+
+fn bd09_to_gcj02(latitude: f64, longitude: f64) -> (f64, f64) {
+    let (x, y) = (longitude - 0.0065, latitude - 0.006);
+    let z = (x * x + y * y).sqrt() - 0.00002 * (y * X_PI).sin();
+    let theta = y.atan2(x) - 0.000003 * (x * X_PI).cos();
+    (z * theta.sin(), z * theta.cos())
+}
+
+impl GeoLocation {
+    pub fn new(latitude: f64, longitude: f64, datum: Datum) -> Self {
+        Self {
+            latitude,
+            longitude,
+            datum,
+        }
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    pub fn datum(&self) -> Datum {
+        self.datum
+    }
+
+    /// The same location in `datum`.
+    pub fn to(&self, datum: Datum) -> Self {
+        if self.datum == datum {
+            return *self;
+        }
+
+        // Everything goes through GCJ-02
+        let gcj02 = match self.datum {
+            Datum::Wgs84 => wgs84_to_gcj02(self.latitude, self.longitude),
+            Datum::Gcj02 => (self.latitude, self.longitude),
+            Datum::Bd09 => bd09_to_gcj02(self.latitude, self.longitude),
+        };
+        let (latitude, longitude) = match datum {
+            Datum::Wgs84 => gcj02_to_wgs84(gcj02.0, gcj02.1),
+            Datum::Gcj02 => gcj02,
+            Datum::Bd09 => gcj02_to_bd09(gcj02.0, gcj02.1),
+        };
+        Self::new(latitude, longitude, datum)
+    }
+
+    /// Value of `GEO`, which is WGS-84 by RFC 5545.
+    pub fn to_ical_str(&self) -> String {
+        let geo = self.to(Datum::Wgs84);
+        format!("{:.6};{:.6}", geo.latitude, geo.longitude)
+    }
+
+    /// Value of `X-APPLE-STRUCTURED-LOCATION`, in GCJ-02 as Apple Maps in China uses.
+    pub fn to_apple_location_str(&self) -> String {
+        let geo = self.to(Datum::Gcj02);
+        format!("geo:{:.6},{:.6}", geo.latitude, geo.longitude)
+    }
+
+    /// Value of ColorOS `EXTENDED-ADDRESS`, in GCJ-02 as its map (Amap) uses.
+    pub fn to_coloros_location_str(&self) -> String {
+        let geo = self.to(Datum::Gcj02);
+        format!(
+            r#"{{"EVENT_ADDRESS":"{{\"lat\":{:.6},\"lon\":{:.6}}}"}}"#,
+            geo.latitude, geo.longitude
+        )
+    }
+
+    /// Links that open `name` at this location in map apps, as lines of event notes.
+    pub fn map_links(&self, name: &str) -> Vec<String> {
+        let name = url_encode(name);
+        let gcj02 = self.to(Datum::Gcj02);
+        let bd09 = self.to(Datum::Bd09);
+
+        vec![
+            format!(
+                "高德地图：https://uri.amap.com/marker?position={:.6},{:.6}&name={name}&coordinate=gaode",
+                gcj02.longitude, gcj02.latitude
+            ),
+            format!(
+                "百度地图：https://api.map.baidu.com/marker?location={:.6},{:.6}&title={name}&content={name}&coord_type=bd09ll&output=html",
+                bd09.latitude, bd09.longitude
+            ),
+            format!(
+                "Apple地图：https://maps.apple.com/?ll={:.6},{:.6}&q={name}",
+                gcj02.latitude, gcj02.longitude
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 仙Ⅱ, as Apple Maps shows it
+    fn xian_2() -> GeoLocation {
+        GeoLocation::new(32.112285, 118.959041, Datum::Gcj02)
+    }
+
+    fn assert_near(geo: GeoLocation, latitude: f64, longitude: f64) {
+        assert!(
+            (geo.latitude - latitude).abs() < 1e-6 && (geo.longitude - longitude).abs() < 1e-6,
+            "{geo:?} isn't near {latitude},{longitude}"
+        );
+    }
+
+    #[test]
+    fn to_wgs84() {
+        let wgs84 = xian_2().to(Datum::Wgs84);
+        assert_eq!(wgs84.datum(), Datum::Wgs84);
+        assert_near(wgs84, 32.114457, 118.953937);
+    }
+
+    #[test]
+    fn to_bd09() {
+        assert_near(xian_2().to(Datum::Bd09), 32.118119, 118.965573);
+    }
+
+    #[test]
+    fn round_trips() {
+        let xian_2 = xian_2();
+        for datum in [Datum::Wgs84, Datum::Bd09] {
+            let back = xian_2.to(datum).to(Datum::Gcj02);
+            assert_near(back, xian_2.latitude, xian_2.longitude);
+        }
+
+        let wgs84 = xian_2.to(Datum::Wgs84);
+        assert_near(
+            wgs84.to(Datum::Bd09).to(Datum::Wgs84),
+            wgs84.latitude,
+            wgs84.longitude,
+        );
+    }
+
+    #[test]
+    fn outside_china_has_no_offset() {
+        let greenwich = GeoLocation::new(51.477928, -0.001545, Datum::Wgs84);
+        assert_near(greenwich.to(Datum::Gcj02), 51.477928, -0.001545);
+    }
+
+    #[test]
+    fn ical_is_wgs84() {
+        assert_eq!(xian_2().to_ical_str(), "32.114457;118.953937");
+    }
+}
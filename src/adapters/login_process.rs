@@ -132,17 +132,22 @@ impl LoginProcess {
         }
     }
 
-    pub async fn selected_school_adapter_name(&self) -> Option<String> {
+    /// The school adapter selected, if any
+    pub async fn selected_school(&self) -> Option<Arc<dyn School>> {
         let inner = self.inner.lock().await;
 
         match &inner.state {
             LoginProcessState::Started { .. } => None,
-            LoginProcessState::SelectedSchool { school, .. } => {
-                Some(school.adapter_name().to_string())
-            }
-            LoginProcessState::Finished { school, .. } => Some(school.adapter_name().to_string()),
+            LoginProcessState::SelectedSchool { school, .. }
+            | LoginProcessState::Finished { school, .. } => Some(school.clone()),
         }
     }
+
+    pub async fn selected_school_adapter_name(&self) -> Option<String> {
+        self.selected_school()
+            .await
+            .map(|school| school.adapter_name().to_string())
+    }
 }
 
 impl<S: Sync> FromRequestParts<S> for LoginProcess {
@@ -0,0 +1,23 @@
+//! Database migrations, in namespaces.
+//!
+//! Each adapter keeps its migrations in its own directory under `migrations/`, and so
+//! does the server itself (`migrations/server`). They all record applied versions in
+//! the same `_sqlx_migrations` table, so each namespace ignores versions applied by others.
+//! Versions are timestamps, which keeps them from colliding.
+
+use anyhow::{Context, Result};
+use sqlx::SqlitePool;
+use sqlx::migrate::Migrator;
+
+/// Run migrations of a namespace.
+///
+/// ```ignore
+/// migrations::run(sqlx::migrate!("./migrations/server"), "server", &db).await?;
+/// ```
+pub async fn run(mut migrator: Migrator, namespace: &str, db: &SqlitePool) -> Result<()> {
+    migrator.set_ignore_missing(true);
+    migrator
+        .run(db)
+        .await
+        .with_context(|| format!("Failed to migrate database for {namespace}"))
+}
@@ -1,5 +1,12 @@
+pub mod buildings;
 pub mod course;
+pub mod crypto;
+pub mod geo;
 pub mod login_process;
+pub mod migrations;
+pub mod recurrence;
+pub mod semester;
+pub mod timezone;
 pub mod traits;
 
 pub mod nju_graduate;
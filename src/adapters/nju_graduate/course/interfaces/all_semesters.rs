@@ -1,3 +1,4 @@
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::Deserialize;
@@ -41,6 +42,7 @@ impl Response {
             .post("https://ehallapp.nju.edu.cn/gsapp/sys/wdkbapp/modules/xskcb/kfdxnxqcx.do")
             .send()
             .await?
+            .check_login()?
             .json()
             .await
             .context("Parsing response of all semesters for nju graduate student")
@@ -1,5 +1,6 @@
 //! 对应课表页面下面的表格。这里的课程时间机器可读性很差，但有校区信息。
 
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use map_macro::hash_map;
 use reqwest_middleware::ClientWithMiddleware;
@@ -53,6 +54,6 @@ impl Response {
 
         client.post("https://ehallapp.nju.edu.cn/gsapp/sys/wdkbapp/modules/xskcb/xsjxrwcx.do?_=1765716674587")
                 .form(&form)
-                .send().await?.json().await.context("Parsing course list for nju graduate student")
+                .send().await?.check_login()?.json().await.context("Parsing course list for nju graduate student")
     }
 }
@@ -9,7 +9,9 @@ use reqwest_middleware::ClientWithMiddleware;
 use serde::Deserialize;
 use tracing::instrument;
 
-use crate::adapters::course::Course;
+use crate::adapters::buildings::BuildingRegistry;
+use crate::adapters::course::{Course, CourseKind, slot_id, split_names, weeks_from_bitmap};
+use crate::adapters::nju_undergrad::login::CheckLogin;
 
 #[derive(Deserialize, Debug)]
 pub struct Response {
@@ -81,6 +83,7 @@ impl Response {
             .form(&form)
             .send()
             .await?
+            .check_login()?
             .json()
             .await
             .context("Parsing schedule courses for nju graduate")
@@ -92,7 +95,15 @@ impl Row {
         &self,
         courseid_to_campus: &HashMap<String, String>,
         semester_start: &NaiveDate,
+        buildings: &BuildingRegistry,
     ) -> Course {
+        let location = Some(self.JASMC.clone()).filter(|location| !location.is_empty());
+        let campus = courseid_to_campus.get(&self.KCDM).cloned().or_else(|| {
+            // Not in the course list, so tell from the room
+            buildings
+                .building(location.as_deref(), self.JASDM.as_deref(), None)
+                .map(|building| format!("{}校区", building.campus))
+        });
         let (start, end) = self.get_time();
         let times: Vec<_> = self
             .get_dates(semester_start)
@@ -114,20 +125,28 @@ impl Row {
 
         Course {
             name: self.KCMC.clone(),
+            id: slot_id(&self.BJMC, self.XQ, self.KSJCDM, self.JSJCDM),
+            semester: self.XNXQDM.clone(),
+            kind: CourseKind::Lecture,
             time: times,
-            location: Some(self.JASMC.clone()),
-            geo: None,
-            campus: courseid_to_campus.get(&self.KCDM).cloned(),
-            notes: vec![
-                format!("教师：{}", self.JSXM.clone()),
-                format!(
-                    "选课备注：{}",
-                    self.XKBZ
-                        .as_ref()
-                        .cloned()
-                        .unwrap_or_else(|| "无备注".to_string())
-                ),
-            ],
+            geo: buildings.locate(
+                location.as_deref(),
+                self.JASDM.as_deref(),
+                campus.as_deref(),
+            ),
+            location,
+            campus,
+            location_id: self.JASDM.clone(),
+            code: Some(self.KCDM.clone()),
+            class_name: Some(self.BJMC.clone()),
+            teachers: split_names(&self.JSXM),
+            credits: None,
+            attending_classes: vec![],
+            remarks: self.XKBZ.clone(),
+            weeks: weeks_from_bitmap(&self.ZCBH),
+            periods: Some((self.KSJCDM as u32, self.JSJCDM as u32)),
+            all_day: false,
+            notes: vec![],
         }
     }
 
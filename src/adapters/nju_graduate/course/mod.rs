@@ -1,5 +1,6 @@
 use crate::adapters::nju_graduate::course::utils::group_by;
-use crate::adapters::{course::Course, nju_graduate::NJUGraduateAdapter, traits::CoursesProvider};
+use crate::adapters::semester::{Semester, Timetable};
+use crate::adapters::{nju_graduate::NJUGraduateAdapter, traits::CoursesProvider};
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::{Duration, NaiveDateTime};
@@ -122,7 +123,7 @@ async fn merge_courses(
 
 #[async_trait]
 impl CoursesProvider for NJUGraduateAdapter {
-    async fn courses(&self, client: &ClientWithMiddleware) -> Result<Vec<Course>> {
+    async fn courses(&self, client: &ClientWithMiddleware) -> Result<Timetable> {
         let (semester_start, curr_semester_id) = get_curr_semester(client).await?;
 
         let courses = CoursesResponse::from_req(client, &curr_semester_id).await?;
@@ -133,10 +134,17 @@ impl CoursesProvider for NJUGraduateAdapter {
 
         let courses = merged_courses
             .iter()
-            .map(|x| x.to_course(&courseid_to_campus, &semester_start.date()))
+            .map(|x| x.to_course(&courseid_to_campus, &semester_start.date(), &self.buildings))
             .collect();
 
-        Ok(courses)
+        Ok(Timetable {
+            semester: Some(Semester {
+                id: curr_semester_id,
+                start: semester_start.date(),
+                end: None,
+            }),
+            courses,
+        })
     }
 }
 
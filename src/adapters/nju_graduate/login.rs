@@ -1,77 +1,37 @@
-use crate::adapters::nju_undergrad::NJUUndergradAdaptor;
-use crate::adapters::nju_undergrad::login::LoginCredential;
-use crate::adapters::traits::School;
-use crate::adapters::{
-    nju_graduate::NJUGraduateAdapter,
-    traits::{Credentials, Login, LoginSession},
-};
+use crate::adapters::nju_graduate::NJUGraduateAdapter;
+use crate::adapters::nju_undergrad::login::{Session, create_authenticated_client};
+use crate::adapters::traits::{Credentials, Login, LoginSession};
 use anyhow::Result;
-use anyhow::anyhow;
 use async_trait::async_trait;
-use reqwest::Url;
-use reqwest::cookie::Jar;
 use reqwest_middleware::ClientWithMiddleware;
-use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
-use reqwest_tracing::TracingMiddleware;
-use std::sync::Arc;
-
-impl NJUGraduateAdapter {
-    async fn to_dummy_undergraduate(&self) -> NJUUndergradAdaptor {
-        NJUUndergradAdaptor::new(self.connection.clone()).await
-    }
-}
 
+// We use the same login as undergraduates, except for the app on ehall
 #[async_trait]
 impl Login for NJUGraduateAdapter {
     async fn new_login_session(&self) -> Result<Box<dyn LoginSession>> {
-        self.to_dummy_undergraduate()
-            .await
-            .new_login_session()
-            .await
+        Ok(Box::new(Session::new(self.credentials.clone()).await?))
     }
 
     async fn get_cred_from_db(&self, session_id: &str) -> Option<Box<dyn Credentials>> {
-        self.to_dummy_undergraduate()
-            .await
-            .get_cred_from_db(session_id)
-            .await
+        Some(Box::new(self.credentials.get(session_id).await?))
+    }
+
+    async fn delete_cred_from_db(&self, db_key: &str) -> Result<bool> {
+        self.credentials.delete(db_key).await
+    }
+
+    async fn purge_unused_creds(&self, unused_since: chrono::NaiveDateTime) -> Result<u64> {
+        self.credentials.purge_unused(unused_since).await
     }
 
-    //  Copied from nju_undergrad::login, except that changed the final appId used to get some cookies
     async fn create_authenticated_client(
         &self,
         credentials: Box<dyn Credentials>,
     ) -> Result<ClientWithMiddleware> {
-        let jar = Arc::new(Jar::default());
-
-        let client = reqwest_middleware::ClientBuilder::new(
-            reqwest::Client::builder()
-                .cookie_provider(jar.clone())
-                .user_agent("nju-schedule-ics")
-                .timeout(std::time::Duration::from_secs(10))
-                .build()?,
-        )
-        .with(RetryTransientMiddleware::new_with_policy(
-            ExponentialBackoff::builder().build_with_max_retries(3),
-        ))
-        .with(TracingMiddleware::default())
-        .build();
-
-        let credentials: Box<LoginCredential> = credentials
-            .downcast()
-            .map_err(|_| anyhow!("Invalid login credentials (failed to downcast)"))?;
-        jar.add_cookie_str(
-            format!("CASTGC={}", credentials.value).as_str(),
-            &Url::parse("https://authserver.nju.edu.cn").unwrap(),
-        );
-
-        let _ = client
-            .get("https://ehall.nju.edu.cn/appShow?appId=4979568947762216")
-            .send()
-            .await?
-            .text()
-            .await?;
+        create_authenticated_client(credentials, "4979568947762216").await
+    }
 
-        Ok(client)
+    async fn mark_cred_stale(&self, db_key: &str) -> Result<()> {
+        self.credentials.mark_stale(db_key).await
     }
 }
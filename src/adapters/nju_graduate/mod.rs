@@ -2,11 +2,14 @@
 
 use std::sync::Arc;
 
+use anyhow::Result;
 use async_trait::async_trait;
 use derivative::Derivative;
 use sqlx::SqlitePool;
 use tokio::sync::Mutex;
 
+use crate::adapters::buildings::BuildingRegistry;
+use crate::adapters::nju_undergrad::login::CredentialStore;
 use crate::adapters::traits::{CalendarHelper, School};
 mod course;
 mod login;
@@ -14,34 +17,41 @@ mod login;
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct NJUGraduateAdapter {
+    credentials: CredentialStore,
     #[derivative(Debug = "ignore")]
-    connection: Arc<Mutex<SqlitePool>>,
+    buildings: Arc<BuildingRegistry>,
+}
+
+const NAME: &str = "南京大学研究生";
+
+impl NJUGraduateAdapter {
+    /// Create an instance that finds where courses take place with `buildings`.
+    pub async fn with_buildings(
+        db: Arc<Mutex<SqlitePool>>,
+        buildings: Arc<BuildingRegistry>,
+    ) -> Result<Self> {
+        // We use the same login as undergraduates
+        CredentialStore::migrate(&*db.lock().await).await?;
+
+        Ok(Self {
+            credentials: CredentialStore::new(db, NAME),
+            buildings,
+        })
+    }
 }
 
 #[async_trait]
 impl School for NJUGraduateAdapter {
-    async fn new(db: Arc<Mutex<SqlitePool>>) -> Self
+    /// Only with bundled buildings, see [`Self::with_buildings`].
+    async fn new(db: Arc<Mutex<SqlitePool>>) -> Result<Self>
     where
         Self: Sized,
     {
-        {
-            let database = db.lock().await;
-            let _res = sqlx::query(
-                "CREATE TABLE IF NOT EXISTS castgc (
-                    key TEXT PRIMARY KEY,
-                    value TEXT NOT NULL,
-                    last_access TEXT NOT NULL
-                )",
-            )
-            .execute(&*database)
-            .await
-            .expect("Failed to ensure table exists");
-        }
-        Self { connection: db }
+        Self::with_buildings(db, Arc::new(BuildingRegistry::bundled()?)).await
     }
 
     fn adapter_name(&self) -> &str {
-        "南京大学研究生"
+        NAME
     }
 }
 
@@ -1,35 +1,43 @@
 use super::interfaces;
-use crate::adapters::{course::Course, course::GeoLocation};
+use crate::adapters::buildings::BuildingRegistry;
+use crate::adapters::course::{Course, CourseKind, slot_id, split_names, weeks_from_bitmap};
+use crate::adapters::semester::{Semester, Timetable};
 use anyhow::{Result, anyhow, bail};
 use chrono::{NaiveDate, NaiveTime, Utc};
 use reqwest_middleware::ClientWithMiddleware;
 use tracing::{Level, debug, event, instrument};
 
-#[instrument(err, ret)]
-pub async fn get_courses(client: &ClientWithMiddleware) -> Result<Vec<Course>> {
+#[instrument(skip(buildings), err, ret)]
+pub async fn get_courses(
+    client: &ClientWithMiddleware,
+    buildings: &BuildingRegistry,
+) -> Result<Timetable> {
     let current_semester = get_current_semester_id(client).await?;
     let courses = interfaces::courses::Response::from_req(client, &current_semester).await?;
     let final_exams =
         interfaces::final_exams::Response::from_req(client, &current_semester).await?;
-    let semester_start = get_semester_start(client, &current_semester).await?;
+    let semester = get_semester(client, &current_semester).await?;
 
     let result: Vec<Course> = courses
         .datas
         .cxxszhxqkb
         .rows
         .into_iter()
-        .map(|course_json| course_json.into_course(&semester_start))
+        .map(|course_json| course_json.into_course(&current_semester, &semester.start, buildings))
         .chain(
             final_exams
                 .datas
                 .cxxsksap
                 .rows
                 .into_iter()
-                .map(|exam| exam.into_course()),
+                .map(|exam| exam.into_course(&current_semester, buildings)),
         )
         .collect();
 
-    Ok(result)
+    Ok(Timetable {
+        semester: Some(semester),
+        courses: result,
+    })
 }
 
 #[instrument(err, ret)]
@@ -47,10 +55,10 @@ async fn get_current_semester_id(client: &ClientWithMiddleware) -> Result<String
 }
 
 #[instrument(err, ret)]
-async fn get_semester_start(
+async fn get_semester(
     client: &ClientWithMiddleware,
     current_semester_id: &str,
-) -> Result<chrono::NaiveDate> {
+) -> Result<Semester> {
     let all_semesters = interfaces::all_semesters::Response::from_req(client).await?;
 
     let curr_semester_info = all_semesters
@@ -69,6 +77,11 @@ async fn get_semester_start(
         )
     };
     let start_date = NaiveDate::parse_from_str(start_date, "%Y-%m-%d")?;
+    let end_date = curr_semester_info
+        .XQJSRQ
+        .as_deref()
+        .and_then(|end| end.split(' ').next())
+        .and_then(|end| NaiveDate::parse_from_str(end, "%Y-%m-%d").ok());
 
     event!(
         Level::DEBUG,
@@ -78,12 +91,16 @@ async fn get_semester_start(
         start_date = format!("{:?}", start_date)
     );
 
-    Ok(start_date)
+    Ok(Semester {
+        id: current_semester_id.to_string(),
+        start: start_date,
+        end: end_date,
+    })
 }
 
 impl interfaces::final_exams::Row {
-    #[instrument]
-    pub fn into_course(self) -> Course {
+    #[instrument(skip(buildings))]
+    pub fn into_course(self, semester: &str, buildings: &BuildingRegistry) -> Course {
         let offset = chrono::FixedOffset::east_opt(8 * 60 * 60).unwrap();
 
         let date = NaiveDate::parse_from_str(&self.KSRQ, "%Y-%m-%d").ok();
@@ -106,18 +123,37 @@ impl interfaces::final_exams::Row {
 
         Course {
             name: format!("{}期末考试", self.KCM),
+            // A course may have more than one exam
+            id: format!("{}期末考试/{}", self.KCM, self.KSRQ),
+            semester: semester.to_string(),
+            kind: CourseKind::Exam,
             time,
             location: Some(self.JASMC.clone()),
-            geo: GeoLocation::from_name_and_campus(&self.JASMC, ""),
+            geo: buildings.locate(Some(&self.JASMC), None, None),
             campus: None,
-            notes: vec![format!("教师：{}", &self.ZJJSXM)],
+            location_id: None,
+            code: self.KCH,
+            class_name: None,
+            teachers: split_names(&self.ZJJSXM),
+            credits: None,
+            attending_classes: vec![],
+            remarks: None,
+            weeks: vec![],
+            periods: None,
+            all_day: false,
+            notes: vec![],
         }
     }
 }
 
 impl interfaces::courses::Course {
-    #[instrument]
-    pub fn into_course(self, semester_start: &chrono::NaiveDate) -> Course {
+    #[instrument(skip(buildings))]
+    pub fn into_course(
+        self,
+        semester: &str,
+        semester_start: &chrono::NaiveDate,
+        buildings: &BuildingRegistry,
+    ) -> Course {
         let time = self.get_time();
         let all_course_times = match time {
             Some((start, end)) => self
@@ -142,24 +178,39 @@ impl interfaces::courses::Course {
         };
 
         Course {
+            id: slot_id(
+                self.JXBMC
+                    .as_deref()
+                    .or(self.KCM.as_deref())
+                    .unwrap_or("未知课程"),
+                self.SKXQ,
+                self.KSJC,
+                self.JSJC,
+            ),
             name: self.KCM.unwrap_or_else(|| "未知课程".to_string()),
+            semester: semester.to_string(),
+            kind: CourseKind::Lecture,
             time: all_course_times,
-            geo: match (&self.JASMC, &self.XXXQDM_DISPLAY) {
-                (Some(location), Some(campus)) => {
-                    GeoLocation::from_name_and_campus(location, campus)
-                }
-                _ => None,
-            },
+            geo: buildings.locate(
+                self.JASMC.as_deref(),
+                self.JASDM.as_deref(),
+                self.XXXQDM_DISPLAY.as_deref(),
+            ),
             location: self.JASMC,
             campus: self.XXXQDM_DISPLAY,
-            notes: vec![
-                format!("班级: {}", self.JXBMC.unwrap_or_else(|| "未知".to_string())),
-                format!("教师: {}", self.JSHS.unwrap_or_else(|| "未知".to_string())),
-                format!(
-                    "上课班级: {}",
-                    self.SKBJ.unwrap_or_else(|| "未知".to_string())
-                ),
-            ],
+            location_id: self.JASDM,
+            code: self.KCH,
+            class_name: self.JXBMC,
+            teachers: self.SKJS.as_deref().map(split_names).unwrap_or_default(),
+            credits: self.XF,
+            attending_classes: self.SKBJ.as_deref().map(split_names).unwrap_or_default(),
+            remarks: None,
+            weeks: weeks_from_bitmap(&self.SKZC),
+            // 0 for free-time courses
+            periods: (self.KSJC > 0 && self.JSJC > 0)
+                .then_some((self.KSJC as u32, self.JSJC as u32)),
+            all_day: false,
+            notes: vec![],
         }
     }
 
@@ -227,3 +278,68 @@ impl interfaces::courses::Course {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A row of 高等数学 01班 on `weekday` at periods `first`-`last`, in weeks 1-16.
+    fn row(weekday: i32, first: i32, last: i32) -> interfaces::courses::Course {
+        serde_json::from_value(json!({
+            "KCM": "高等数学",
+            "JXBMC": "高等数学01班",
+            "JASMC": "仙Ⅱ-207",
+            "KSJC": first.to_string(),
+            "JSJC": last.to_string(),
+            "SKXQ": weekday.to_string(),
+            "SKZC": "1111111111111111",
+            "XXXQDM": "3",
+            "XXXQDM_DISPLAY": "仙林校区",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn class_with_two_slots_has_unique_uids() {
+        let start = NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        let buildings = BuildingRegistry::default();
+        let courses = [row(1, 1, 2), row(3, 3, 4)]
+            .map(|row| row.into_course("2025-2026-1", &start, &buildings));
+
+        let mut uids: Vec<_> = courses
+            .iter()
+            .flat_map(|course| {
+                course
+                    .recurrences()
+                    .into_iter()
+                    .map(|(key, _)| course.event_uid("南京大学本科生", &key))
+            })
+            .collect();
+        assert_eq!(uids.len(), 2);
+        uids.dedup();
+        assert_eq!(uids.len(), 2);
+        assert_eq!(courses[0].id, "高等数学01班/周1/1-2节");
+    }
+
+    #[test]
+    fn exams_of_a_course_have_unique_ids() {
+        let exam = |date: &str| -> interfaces::final_exams::Row {
+            serde_json::from_value(json!({
+                "JASMC": "仙Ⅱ-105",
+                "KSKSSJ": "14:00",
+                "KSJSSJ": "16:00",
+                "XH": "000000000",
+                "KSRQ": date,
+                "KCM": "高等数学",
+                "ZJJSXM": "张三",
+            }))
+            .unwrap()
+        };
+        let buildings = BuildingRegistry::default();
+        let first = exam("2026-01-05").into_course("2025-2026-1", &buildings);
+        let second = exam("2026-01-12").into_course("2025-2026-1", &buildings);
+
+        assert_ne!(first.id, second.id);
+    }
+}
@@ -4,6 +4,7 @@
 //! This includes the start date of every semester.
 #![allow(non_snake_case)]
 
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::Deserialize;
@@ -34,6 +35,8 @@ pub struct Semester {
     pub XQ: String,
     /// Semester start date e.g. "2025-08-25 00:00:00"
     pub XQKSRQ: String,
+    /// Semester end date e.g. "2026-01-18 00:00:00"
+    pub XQJSRQ: Option<String>,
 }
 
 impl Response {
@@ -43,6 +46,7 @@ impl Response {
             .get("https://ehallapp.nju.edu.cn/jwapp/sys/wdkb/modules/jshkcb/cxjcs.do")
             .send()
             .await?
+            .check_login()?
             .json()
             .await
             .context("Parsing all semesters for nju undergrad")
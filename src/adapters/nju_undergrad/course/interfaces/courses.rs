@@ -2,6 +2,7 @@
 //! URL: https://ehallapp.nju.edu.cn/jwapp/sys/wdkb/modules/xskcb/cxxszhxqkb.do
 #![allow(non_snake_case)]
 
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use map_macro::hash_map;
 use reqwest_middleware::ClientWithMiddleware;
@@ -33,6 +34,8 @@ pub struct Cxxszhxqkb {
 pub struct Course {
     /// Course name
     pub KCM: Option<String>,
+    /// Course code (课程号)
+    pub KCH: Option<String>,
     /// Teacher
     pub SKJS: Option<String>,
     /// Teacher with work number e.g. "1507810 王可 "
@@ -92,6 +95,7 @@ impl Response {
             .form(&form)
             .send()
             .await?
+            .check_login()?
             .json()
             .await
             .context("Parsing courses for nju undergrad")
@@ -2,6 +2,7 @@
 //! URL: https://ehallapp.nju.edu.cn/jwapp/sys/wdkb/modules/jshkcb/dqxnxq.do
 #![allow(non_snake_case)]
 
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::Deserialize;
@@ -43,6 +44,7 @@ impl Response {
             .get("https://ehallapp.nju.edu.cn/jwapp/sys/wdkb/modules/jshkcb/dqxnxq.do")
             .send()
             .await?
+            .check_login()?
             .json()
             .await
             .context("Parsing current semester for nju undergrad")
@@ -2,6 +2,7 @@
 //! URL: https://ehallapp.nju.edu.cn/jwapp/sys/studentWdksapApp/WdksapController/cxxsksap.do
 #![allow(non_snake_case)]
 
+use crate::adapters::nju_undergrad::login::CheckLogin;
 use anyhow::{Context, Result};
 use map_macro::hash_map;
 use reqwest_middleware::ClientWithMiddleware;
@@ -42,6 +43,8 @@ pub struct Row {
     pub KSRQ: String,
     /// 课程名
     pub KCM: String,
+    /// 课程号
+    pub KCH: Option<String>,
     /// 教师姓名
     pub ZJJSXM: String,
 }
@@ -65,6 +68,7 @@ impl Response {
             .form(&form)
             .send()
             .await?
+            .check_login()?
             .json()
             .await.context("Parsing response for final exams of nju under graduate")
     }
@@ -1,6 +1,8 @@
 use super::NJUUndergradAdaptor;
 
-use crate::adapters::traits::{Credentials, Login, LoginSession};
+use crate::adapters::crypto;
+use crate::adapters::migrations;
+use crate::adapters::traits::{CredentialExpired, Credentials, Login, LoginSession};
 use aes::{
     Aes128,
     cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7},
@@ -22,71 +24,109 @@ use sqlx::prelude::FromRow;
 use std::sync::Arc;
 use std::{collections::HashMap, io::Cursor};
 use tokio::sync::Mutex;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 // use xee_xpath::{DocumentHandle, Documents, Queries, Query};
 
 #[async_trait]
 impl Login for NJUUndergradAdaptor {
     async fn new_login_session(&self) -> Result<Box<dyn LoginSession>> {
-        Ok(Box::new(Session::new(self.connection.clone()).await?))
+        Ok(Box::new(Session::new(self.credentials.clone()).await?))
     }
 
     async fn get_cred_from_db(&self, session_id: &str) -> Option<Box<dyn Credentials>> {
-        let connection = self.connection.lock().await;
+        Some(Box::new(self.credentials.get(session_id).await?))
+    }
 
-        let mut cred = sqlx::query_as::<_, LoginCredential>("SELECT * FROM castgc WHERE key = ?")
-            .bind(session_id)
-            .fetch_one(&*connection)
-            .await
-            .ok()?;
-        cred.last_access = chrono::Local::now().naive_local();
+    async fn delete_cred_from_db(&self, db_key: &str) -> Result<bool> {
+        self.credentials.delete(db_key).await
+    }
 
-        Some(Box::new(cred))
+    async fn purge_unused_creds(&self, unused_since: chrono::NaiveDateTime) -> Result<u64> {
+        self.credentials.purge_unused(unused_since).await
     }
 
     async fn create_authenticated_client(
         &self,
         credentials: Box<dyn Credentials>,
     ) -> Result<ClientWithMiddleware> {
-        let jar = Arc::new(Jar::default());
-
-        let client = reqwest_middleware::ClientBuilder::new(
-            reqwest::ClientBuilder::new()
-                .cookie_provider(jar.clone())
-                .user_agent("nju-schedule-ics")
-                .timeout(std::time::Duration::from_secs(10))
-                .build()?,
-        )
-        .with(RetryTransientMiddleware::new_with_policy(
-            ExponentialBackoff::builder().build_with_max_retries(3),
-        ))
-        .with(TracingMiddleware::default())
-        .build();
+        create_authenticated_client(credentials, "4770397878132218").await
+    }
 
-        let credentials: Box<LoginCredential> = credentials
-            .downcast()
-            .map_err(|_| anyhow!("Invalid login credentials (failed to downcast)"))?;
-        jar.add_cookie_str(
-            format!("CASTGC={}", credentials.value).as_str(),
-            &Url::parse("https://authserver.nju.edu.cn").unwrap(),
-        );
+    async fn mark_cred_stale(&self, db_key: &str) -> Result<()> {
+        self.credentials.mark_stale(db_key).await
+    }
+}
 
-        let _ = client
-            .get("https://ehall.nju.edu.cn/appShow?appId=4770397878132218")
-            .send()
-            .await?
-            .text()
-            .await?;
+/// Create an HTTP client logged in with the CASTGC cookie, then open the app `app_id`
+/// on ehall so that it gives us cookies for its API.
+pub async fn create_authenticated_client(
+    credentials: Box<dyn Credentials>,
+    app_id: &str,
+) -> Result<ClientWithMiddleware> {
+    let jar = Arc::new(Jar::default());
 
-        Ok(client)
+    let client = reqwest_middleware::ClientBuilder::new(
+        reqwest::ClientBuilder::new()
+            .cookie_provider(jar.clone())
+            .user_agent("nju-schedule-ics")
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?,
+    )
+    .with(RetryTransientMiddleware::new_with_policy(
+        ExponentialBackoff::builder().build_with_max_retries(3),
+    ))
+    .with(TracingMiddleware::default())
+    .build();
+
+    let credentials: Box<LoginCredential> = credentials
+        .downcast()
+        .map_err(|_| anyhow!("Invalid login credentials (failed to downcast)"))?;
+    if credentials.stale {
+        return Err(CredentialExpired.into());
+    }
+    jar.add_cookie_str(
+        format!("CASTGC={}", credentials.value).as_str(),
+        &Url::parse("https://authserver.nju.edu.cn").unwrap(),
+    );
+
+    let response = client
+        .get(format!("https://ehall.nju.edu.cn/appShow?appId={app_id}"))
+        .send()
+        .await?;
+    let _ = response.check_login()?.text().await?;
+
+    Ok(client)
+}
+
+/// Whether we were redirected to the login page, which means the CASTGC cookie
+/// is no longer valid.
+pub fn is_login_page(url: &Url) -> bool {
+    url.host_str() == Some("authserver.nju.edu.cn") && url.path().starts_with("/authserver/login")
+}
+
+/// Checks responses of a logged in client.
+pub trait CheckLogin: Sized {
+    /// Fail with [`CredentialExpired`] if we were redirected to the login page.
+    ///
+    /// The school may stop accepting the CASTGC cookie at any request, not only when we
+    /// open the app, so check every response before parsing it.
+    fn check_login(self) -> Result<Self>;
+}
+
+impl CheckLogin for reqwest::Response {
+    fn check_login(self) -> Result<Self> {
+        if is_login_page(self.url()) {
+            return Err(CredentialExpired.into());
+        }
+        Ok(self)
     }
 }
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct Session {
-    db: Arc<Mutex<SqlitePool>>,
+    credentials: CredentialStore,
     id: String,
     client: ClientWithMiddleware,
     #[derivative(Debug = "ignore")]
@@ -132,6 +172,7 @@ impl LoginSession for Session {
                 key: self.id.clone(),
                 value: castgc_cookie.value().to_string(),
                 last_access: chrono::Local::now().naive_local(),
+                stale: false,
             })),
             // Login failed, try to get reason from webpage
             None => {
@@ -161,18 +202,9 @@ impl LoginSession for Session {
         let cred: Box<LoginCredential> = cred
             .downcast()
             .map_err(|_| anyhow!("Got invalid credential when saving to db, downcasting failed"))?;
-        let db_key = cred.key.clone();
-
-        let connection = self.db.lock().await;
-        let _inserted =
-            sqlx::query("INSERT INTO castgc (key, value, last_access) VALUES ($1, $2, $3)")
-                .bind(&db_key)
-                .bind(cred.value)
-                .bind(cred.last_access)
-                .execute(&*connection)
-                .await?;
+        self.credentials.save(&cred).await?;
 
-        Ok(db_key)
+        Ok(cred.key)
     }
 }
 
@@ -180,7 +212,7 @@ impl Session {
     /// Create a login session
     ///
     /// by requesting the login page
-    pub async fn new(db: Arc<Mutex<SqlitePool>>) -> Result<Self> {
+    pub async fn new(credentials: CredentialStore) -> Result<Self> {
         let (client, _jar) = build_client().await?;
 
         debug!("Requesting login page");
@@ -207,7 +239,7 @@ impl Session {
             client,
             captcha: captcha_image,
             context,
-            db,
+            credentials,
         })
     }
 }
@@ -289,6 +321,163 @@ pub struct LoginCredential {
     pub value: String,
     /// Time last accessed
     pub last_access: chrono::NaiveDateTime,
+    /// Whether the school has rejected this cookie
+    pub stale: bool,
+}
+
+/// The `castgc` table, as seen by one adapter.
+///
+/// All NJU adapters log in through the same authserver and share this table,
+/// but each of them only sees credentials created through itself.
+#[derive(Derivative, Clone)]
+#[derivative(Debug)]
+pub struct CredentialStore {
+    #[derivative(Debug = "ignore")]
+    db: Arc<Mutex<SqlitePool>>,
+    adapter: &'static str,
+}
+
+impl CredentialStore {
+    /// `adapter`: name of the adapter the credentials belong to
+    pub fn new(db: Arc<Mutex<SqlitePool>>, adapter: &'static str) -> Self {
+        Self { db, adapter }
+    }
+
+    /// Run migrations for the `castgc` table.
+    pub async fn migrate(db: &SqlitePool) -> Result<()> {
+        migrations::run(
+            sqlx::migrate!("./migrations/nju_undergrad"),
+            "nju_undergrad",
+            db,
+        )
+        .await?;
+
+        Self::encrypt_legacy_rows(db).await
+    }
+
+    /// Older versions stored the subscription key and cookie in plaintext.
+    /// Encrypt them like what [`CredentialStore::save`] does now.
+    async fn encrypt_legacy_rows(db: &SqlitePool) -> Result<()> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT key, value FROM castgc WHERE encrypted = 0")
+                .fetch_all(db)
+                .await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        info!("Encrypting {} stored credentials", rows.len());
+        let mut transaction = db.begin().await?;
+        for (key, value) in rows {
+            sqlx::query("UPDATE castgc SET key = ?, value = ?, encrypted = 1 WHERE key = ?")
+                .bind(crypto::lookup_key(&key))
+                .bind(crypto::seal(&key, &value)?)
+                .bind(&key)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Find a credential of this adapter, and record that it's been used just now.
+    ///
+    /// Credentials saved before we recorded adapters are claimed by whoever uses it first.
+    pub async fn get(&self, key: &str) -> Option<LoginCredential> {
+        let connection = self.db.lock().await;
+
+        let lookup_key = crypto::lookup_key(key);
+
+        let mut cred = sqlx::query_as::<_, LoginCredential>(
+            "SELECT * FROM castgc WHERE key = ? AND (adapter = ? OR adapter IS NULL)",
+        )
+        .bind(&lookup_key)
+        .bind(self.adapter)
+        .fetch_one(&*connection)
+        .await
+        .ok()?;
+        cred.key = key.to_string();
+        cred.value = crypto::open(key, &cred.value)
+            .inspect_err(|error| warn!("Failed to decrypt credential: {error:?}"))
+            .ok()?;
+        cred.last_access = chrono::Local::now().naive_local();
+
+        // Failing to record access time shouldn't stop us from generating the calendar
+        let updated = sqlx::query("UPDATE castgc SET last_access = ?, adapter = ? WHERE key = ?")
+            .bind(cred.last_access)
+            .bind(self.adapter)
+            .bind(&lookup_key)
+            .execute(&*connection)
+            .await;
+        if let Err(error) = updated {
+            warn!("Failed to update last access time: {error:?}");
+        }
+
+        Some(cred)
+    }
+
+    pub async fn save(&self, cred: &LoginCredential) -> Result<()> {
+        let connection = self.db.lock().await;
+        let _inserted = sqlx::query(
+            "INSERT INTO castgc (key, value, last_access, encrypted, adapter)
+            VALUES ($1, $2, $3, 1, $4)",
+        )
+        .bind(crypto::lookup_key(&cred.key))
+        .bind(crypto::seal(&cred.key, &cred.value)?)
+        .bind(cred.last_access)
+        .bind(self.adapter)
+        .execute(&*connection)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns whether there was such a credential.
+    pub async fn delete(&self, key: &str) -> Result<bool> {
+        let connection = self.db.lock().await;
+        let deleted =
+            sqlx::query("DELETE FROM castgc WHERE key = ? AND (adapter = ? OR adapter IS NULL)")
+                .bind(crypto::lookup_key(key))
+                .bind(self.adapter)
+                .execute(&*connection)
+                .await?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    /// Delete credentials not used since `unused_since`, or the school rejected before then.
+    ///
+    /// Returns how many are deleted.
+    pub async fn purge_unused(&self, unused_since: chrono::NaiveDateTime) -> Result<u64> {
+        let connection = self.db.lock().await;
+        let deleted = sqlx::query(
+            "DELETE FROM castgc WHERE (adapter = $1 OR adapter IS NULL)
+            AND (last_access < $2 OR stale_since < $2)",
+        )
+        .bind(self.adapter)
+        .bind(unused_since)
+        .execute(&*connection)
+        .await?;
+
+        Ok(deleted.rows_affected())
+    }
+
+    /// Record that the school rejected the credential, if not recorded yet.
+    pub async fn mark_stale(&self, key: &str) -> Result<()> {
+        let connection = self.db.lock().await;
+        sqlx::query(
+            "UPDATE castgc SET stale = 1, stale_since = COALESCE(stale_since, ?)
+            WHERE key = ? AND adapter = ?",
+        )
+        .bind(chrono::Local::now().naive_local())
+        .bind(crypto::lookup_key(key))
+        .bind(self.adapter)
+        .execute(&*connection)
+        .await?;
+
+        Ok(())
+    }
 }
 
 // === Utils for using xpath easier ===
@@ -316,3 +505,42 @@ impl XpathExt for XpathItemTree {
         Ok(item_set.into_iter().collect())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn legacy_rows_are_encrypted() {
+        // Each connection would have its own in-memory database
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        CredentialStore::migrate(&db).await.unwrap();
+        sqlx::query("INSERT INTO castgc (key, value, last_access) VALUES ('key', 'TGT-1', ?)")
+            .bind(chrono::Local::now().naive_local())
+            .execute(&db)
+            .await
+            .unwrap();
+
+        // Running it again doesn't encrypt twice
+        for _ in 0..2 {
+            CredentialStore::migrate(&db).await.unwrap();
+        }
+
+        let (key, value, encrypted): (String, String, bool) =
+            sqlx::query_as("SELECT key, value, encrypted FROM castgc")
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(key, crypto::lookup_key("key"));
+        assert_ne!(value, "TGT-1");
+        assert!(encrypted);
+
+        let store = CredentialStore::new(Arc::new(Mutex::new(db)), "南京大学本科生");
+        assert_eq!(store.get("key").await.unwrap().value, "TGT-1");
+    }
+}
@@ -1,11 +1,14 @@
 //! 南京大学本科生 适配模块
 mod course;
+use crate::adapters::buildings::BuildingRegistry;
 use crate::adapters::traits::CalendarHelper;
 use sqlx::SqlitePool;
 pub mod login;
 use crate::adapters::traits::School;
+use anyhow::Result;
 use async_trait::async_trait;
 use derivative::Derivative;
+use login::CredentialStore;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -13,35 +16,40 @@ use tokio::sync::Mutex;
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct NJUUndergradAdaptor {
+    credentials: CredentialStore,
     #[derivative(Debug = "ignore")]
-    connection: Arc<Mutex<SqlitePool>>,
+    buildings: Arc<BuildingRegistry>,
+}
+
+const NAME: &str = "南京大学本科生";
+
+impl NJUUndergradAdaptor {
+    /// Create an instance that finds where courses take place with `buildings`.
+    pub async fn with_buildings(
+        db: Arc<Mutex<SqlitePool>>,
+        buildings: Arc<BuildingRegistry>,
+    ) -> Result<Self> {
+        CredentialStore::migrate(&*db.lock().await).await?;
+
+        Ok(Self {
+            credentials: CredentialStore::new(db, NAME),
+            buildings,
+        })
+    }
 }
 
 #[async_trait]
 impl School for NJUUndergradAdaptor {
-    async fn new(db: Arc<Mutex<SqlitePool>>) -> Self
+    /// Only with bundled buildings, see [`Self::with_buildings`].
+    async fn new(db: Arc<Mutex<SqlitePool>>) -> Result<Self>
     where
         Self: Sized,
     {
-        {
-            let database = db.lock().await;
-            let _res = sqlx::query(
-                "CREATE TABLE IF NOT EXISTS castgc (
-                    key TEXT PRIMARY KEY,
-                    value TEXT NOT NULL,
-                    last_access TEXT NOT NULL
-                )",
-            )
-            .execute(&*database)
-            .await
-            .expect("Failed to ensure table exists");
-        }
-
-        Self { connection: db }
+        Self::with_buildings(db, Arc::new(BuildingRegistry::bundled()?)).await
     }
 
     fn adapter_name(&self) -> &str {
-        "南京大学本科生"
+        NAME
     }
 }
 
@@ -0,0 +1,237 @@
+//! Collapses the occurrences of a [`Course`] into weekly recurring series.
+//!
+//! [`Course::time`] lists every occurrence of a course, but most courses happen at the
+//! same time every (other) week. Writing one VEVENT per series with `RRULE` and
+//! `EXDATE` keeps the calendar small, and edits in calendar apps apply to the whole series.
+//!
+//! [`Course`]: crate::adapters::course::Course
+//! [`Course::time`]: crate::adapters::course::Course::time
+
+use chrono::{DateTime, Datelike, Duration, TimeDelta, Timelike, Utc};
+use std::collections::{BTreeMap, BTreeSet};
+
+const UTC_TIME_FMT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Occurrences of a course that happen at the same weekday and time, every `interval` weeks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    /// Start of the first occurrence
+    pub start: DateTime<Utc>,
+    /// Length of each occurrence
+    pub duration: TimeDelta,
+    /// Weeks between two occurrences
+    pub interval: i64,
+    /// Start of the last occurrence
+    pub until: DateTime<Utc>,
+    /// Starts of occurrences in between that don't happen, e.g. skipped weeks and holidays
+    pub exdates: Vec<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// Group occurrences into series.
+    ///
+    /// Occurrences on the same weekday with the same start time and length form a series.
+    /// Its interval is the greatest common divisor of the week gaps, and the missing weeks
+    /// in between become exdates.
+    pub fn from_times(times: &[(DateTime<Utc>, DateTime<Utc>)]) -> Vec<Self> {
+        let mut groups = BTreeMap::<_, BTreeSet<DateTime<Utc>>>::new();
+        for (start, end) in times {
+            let key = (
+                start.weekday().num_days_from_monday(),
+                start.num_seconds_from_midnight(),
+                *end - *start,
+            );
+            groups.entry(key).or_default().insert(*start);
+        }
+
+        let mut result: Vec<_> = groups
+            .into_iter()
+            .map(|((_, _, duration), starts)| Self::from_starts(starts, duration))
+            .collect();
+        result.sort_by_key(|recurrence| recurrence.start);
+        result
+    }
+
+    /// `starts` must be non-empty, on the same weekday and at the same time.
+    fn from_starts(starts: BTreeSet<DateTime<Utc>>, duration: TimeDelta) -> Self {
+        let first = *starts.first().expect("Recurrence without occurrences");
+        let last = *starts.last().expect("Recurrence without occurrences");
+
+        let weeks: Vec<i64> = starts
+            .iter()
+            .map(|start| (*start - first).num_weeks())
+            .collect();
+        let interval = weeks
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .fold(0, gcd)
+            .max(1);
+
+        let exdates = (0..=weeks.last().copied().unwrap_or_default())
+            .step_by(interval as usize)
+            .map(|week| first + Duration::weeks(week))
+            .filter(|start| !starts.contains(start))
+            .collect();
+
+        Self {
+            start: first,
+            duration,
+            interval,
+            until: last,
+            exdates,
+        }
+    }
+
+    /// Whether there is more than one occurrence.
+    pub fn is_recurring(&self) -> bool {
+        self.until > self.start
+    }
+
+    /// End of the first occurrence
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + self.duration
+    }
+
+    /// The `RRULE` value, e.g. `FREQ=WEEKLY;INTERVAL=2;UNTIL=20251231T060000Z`
+    pub fn rrule(&self) -> String {
+        format!(
+            "FREQ=WEEKLY;INTERVAL={};UNTIL={}",
+            self.interval,
+            self.until.format(UTC_TIME_FMT)
+        )
+    }
+
+    /// The `RRULE` value for all-day events, whose `UNTIL` has to be a date, e.g. `20251231`.
+    pub fn date_rrule(&self, until: &str) -> String {
+        format!("FREQ=WEEKLY;INTERVAL={};UNTIL={}", self.interval, until)
+    }
+
+    /// Identifies this series within its course by its slot: weekday, start and end time
+    /// (in UTC), e.g. `1/0000-0150` for Monday 08:00-09:50 in China.
+    ///
+    /// [`Recurrence::from_times`] gives each slot at most one series, and the slot stays the
+    /// same when occurrences are added or removed, e.g. by holidays. A series moved to
+    /// another slot is a new event.
+    pub fn key(&self) -> String {
+        format!(
+            "{}/{}-{}",
+            self.start.weekday().number_from_monday(),
+            self.start.format("%H%M"),
+            self.end().format("%H%M")
+        )
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// `(start, end)` of a class at 08:00-09:50 UTC+8 in `week` (1-based) of a semester
+    /// starting on Monday 2025-09-01, shifted by `days` from Monday.
+    fn class(week: i64, days: i64) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start = Utc.with_ymd_and_hms(2025, 9, 1, 0, 0, 0).unwrap()
+            + Duration::weeks(week - 1)
+            + Duration::days(days);
+        (start, start + Duration::minutes(110))
+    }
+
+    #[test]
+    fn every_week() {
+        let times: Vec<_> = (1..=16).map(|week| class(week, 0)).collect();
+        let recurrences = Recurrence::from_times(&times);
+
+        assert_eq!(recurrences.len(), 1);
+        let recurrence = &recurrences[0];
+        assert_eq!(recurrence.start, class(1, 0).0);
+        assert_eq!(recurrence.until, class(16, 0).0);
+        assert_eq!(recurrence.duration, Duration::minutes(110));
+        assert_eq!(recurrence.interval, 1);
+        assert!(recurrence.exdates.is_empty());
+        assert_eq!(
+            recurrence.rrule(),
+            "FREQ=WEEKLY;INTERVAL=1;UNTIL=20251215T000000Z"
+        );
+    }
+
+    #[test]
+    fn odd_weeks_take_gcd_interval() {
+        let times: Vec<_> = [1, 3, 5, 9, 11].map(|week| class(week, 0)).to_vec();
+        let recurrences = Recurrence::from_times(&times);
+
+        assert_eq!(recurrences.len(), 1);
+        assert_eq!(recurrences[0].interval, 2);
+        assert_eq!(recurrences[0].exdates, vec![class(7, 0).0]);
+    }
+
+    #[test]
+    fn gaps_become_exdates() {
+        let times: Vec<_> = [1, 2, 3, 5, 6, 8].map(|week| class(week, 0)).to_vec();
+        let recurrences = Recurrence::from_times(&times);
+
+        assert_eq!(recurrences.len(), 1);
+        assert_eq!(recurrences[0].interval, 1);
+        assert_eq!(recurrences[0].exdates, vec![class(4, 0).0, class(7, 0).0]);
+    }
+
+    #[test]
+    fn different_weekdays_and_lengths_are_separate_series() {
+        let (start, _) = class(1, 0);
+        let times = vec![
+            class(1, 2),
+            class(1, 0),
+            class(2, 0),
+            (start, start + Duration::minutes(50)),
+        ];
+        let recurrences = Recurrence::from_times(&times);
+
+        assert_eq!(recurrences.len(), 3);
+        // Ordered by first occurrence
+        assert!(
+            recurrences
+                .windows(2)
+                .all(|pair| pair[0].start <= pair[1].start)
+        );
+        let wednesday = recurrences
+            .iter()
+            .find(|recurrence| recurrence.start == class(1, 2).0)
+            .unwrap();
+        assert!(!wednesday.is_recurring());
+        assert_eq!(wednesday.key(), "3/0000-0150");
+
+        let mut keys: Vec<_> = recurrences.iter().map(Recurrence::key).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 3);
+    }
+
+    #[test]
+    fn keys_stay_when_first_occurrence_is_removed() {
+        // Wednesday starts in week 1, Monday in week 2
+        let mut times: Vec<_> = (1..=16).map(|week| class(week, 2)).collect();
+        times.extend((2..=16).map(|week| class(week, 0)));
+        let keys = |times: &[(DateTime<Utc>, DateTime<Utc>)]| -> Vec<String> {
+            let mut keys: Vec<_> = Recurrence::from_times(times)
+                .iter()
+                .map(Recurrence::key)
+                .collect();
+            keys.sort();
+            keys
+        };
+        let before = keys(&times);
+
+        // A holiday in week 1 removes the first Wednesday, so it now starts after Monday
+        times.retain(|time| *time != class(1, 2));
+        assert_eq!(keys(&times), before);
+        assert_eq!(before, ["1/0000-0150", "3/0000-0150"]);
+    }
+
+    #[test]
+    fn no_times() {
+        assert!(Recurrence::from_times(&[]).is_empty());
+    }
+}
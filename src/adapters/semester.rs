@@ -0,0 +1,39 @@
+//! What we get from the school: the current semester and its courses.
+
+use super::course::Course;
+use chrono::{NaiveDate, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+/// A semester, as the school tells.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Semester {
+    /// e.g. `2025-2026-1`, the same as [`Course::semester`]
+    pub id: String,
+    /// Monday of the first week
+    pub start: NaiveDate,
+    /// The last day, if the school tells
+    pub end: Option<NaiveDate>,
+}
+
+impl Semester {
+    /// The week (1-based) that `date` is in, or `None` if it's before the semester.
+    pub fn week_of(&self, date: NaiveDate) -> Option<u32> {
+        let monday = self.start.week(chrono::Weekday::Mon).first_day();
+        let days = (date - monday).num_days();
+        (days >= 0).then(|| (days / 7) as u32 + 1)
+    }
+
+    /// Monday of `week` (1-based).
+    pub fn monday_of(&self, week: u32) -> NaiveDate {
+        let monday = self.start.week(chrono::Weekday::Mon).first_day();
+        monday + TimeDelta::weeks(week as i64 - 1)
+    }
+}
+
+/// Courses of the current semester.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timetable {
+    /// The current semester, if the school tells
+    pub semester: Option<Semester>,
+    pub courses: Vec<Course>,
+}
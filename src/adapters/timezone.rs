@@ -0,0 +1,59 @@
+//! Time zone of a school's timetable, see [`CalendarHelper::timezone`].
+//!
+//! [`CalendarHelper::timezone`]: crate::adapters::traits::CalendarHelper::timezone
+
+use chrono::{DateTime, FixedOffset, Utc};
+use ics::{Standard, TimeZone, properties::TzName};
+
+/// A time zone without daylight saving time.
+#[derive(Debug, Clone, Copy)]
+pub struct SchoolTimeZone {
+    /// IANA name, used as `TZID`, e.g. `Asia/Shanghai`
+    pub tzid: &'static str,
+    /// Abbreviation, e.g. `CST`
+    pub name: &'static str,
+    /// Offset from UTC, in seconds
+    pub utc_offset: i32,
+}
+
+impl SchoolTimeZone {
+    /// China Standard Time
+    pub const ASIA_SHANGHAI: Self = Self {
+        tzid: "Asia/Shanghai",
+        name: "CST",
+        utc_offset: 8 * 60 * 60,
+    };
+
+    pub fn offset(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset).expect("Time zone offset out of bound")
+    }
+
+    /// Format `time` as local time in this time zone, e.g. `20250902T080000`
+    pub fn format_local(&self, time: &DateTime<Utc>) -> String {
+        time.with_timezone(&self.offset())
+            .format("%Y%m%dT%H%M%S")
+            .to_string()
+    }
+
+    /// Format the date of `time` in this time zone, e.g. `20250902`
+    pub fn format_local_date(&self, time: &DateTime<Utc>) -> String {
+        time.with_timezone(&self.offset())
+            .format("%Y%m%d")
+            .to_string()
+    }
+
+    /// The offset in iCalendar format, e.g. `+0800`
+    fn ical_offset(&self) -> String {
+        let sign = if self.utc_offset < 0 { '-' } else { '+' };
+        let minutes = self.utc_offset.abs() / 60;
+        format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+    }
+
+    /// The VTIMEZONE component to put into the calendar.
+    pub fn to_vtimezone<'a>(&self) -> TimeZone<'a> {
+        let mut standard = Standard::new("19700101T000000", self.ical_offset(), self.ical_offset());
+        standard.push(TzName::new(self.name));
+
+        TimeZone::standard(self.tzid, standard)
+    }
+}
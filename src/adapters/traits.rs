@@ -1,4 +1,5 @@
-use super::course::Course;
+use super::semester::Timetable;
+use super::timezone::SchoolTimeZone;
 use anyhow::Result;
 use async_trait::async_trait;
 use downcast_rs::{Downcast, impl_downcast};
@@ -6,7 +7,10 @@ use dyn_clone::DynClone;
 use image::DynamicImage;
 use reqwest_middleware::ClientWithMiddleware;
 use sqlx::SqlitePool;
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+};
 use tokio::sync::Mutex;
 
 /// An adapter for a school API.
@@ -15,8 +19,8 @@ use tokio::sync::Mutex;
 /// multiple [`School`]s here
 #[async_trait]
 pub trait School: Login + CoursesProvider + CalendarHelper + Send + Sync + Debug {
-    /// Create an instance. Do database migrations if needed.
-    async fn new(db: Arc<Mutex<SqlitePool>>) -> Self
+    /// Create an instance, running database migrations of this adapter.
+    async fn new(db: Arc<Mutex<SqlitePool>>) -> Result<Self>
     where
         Self: Sized;
 
@@ -29,22 +33,34 @@ pub trait School: Login + CoursesProvider + CalendarHelper + Send + Sync + Debug
 pub trait Login {
     /// Create a new login session.
     async fn new_login_session(&self) -> Result<Box<dyn LoginSession>>;
-    /// Query login credential in database
+    /// Query login credential in database, and record that it's been used just now.
     async fn get_cred_from_db(&self, db_key: &str) -> Option<Box<dyn Credentials>>;
+    /// Delete login credential from database.
+    ///
+    /// Returns whether there was such a credential.
+    async fn delete_cred_from_db(&self, db_key: &str) -> Result<bool>;
+    /// Delete login credentials not used since `unused_since`, or the school rejected
+    /// before then.
+    ///
+    /// Returns how many are deleted.
+    async fn purge_unused_creds(&self, unused_since: chrono::NaiveDateTime) -> Result<u64>;
     /// Create an HTTP client given the login credentials.
     ///
-    /// This client is logged in.
+    /// This client is logged in. If the school no longer accepts the credentials,
+    /// this should fail with [`CredentialExpired`].
     async fn create_authenticated_client(
         &self,
         credentials: Box<dyn Credentials>,
     ) -> Result<ClientWithMiddleware>;
+    /// Mark the credential in database as expired, so that we don't try it again.
+    async fn mark_cred_stale(&self, db_key: &str) -> Result<()>;
 }
 
 /// Supports getting courses from school.
 #[async_trait]
 pub trait CoursesProvider {
-    /// Get courses
-    async fn courses(&self, client: &ClientWithMiddleware) -> Result<Vec<Course>>;
+    /// Get courses of the current semester
+    async fn courses(&self, client: &ClientWithMiddleware) -> Result<Timetable>;
 }
 
 /// The login credential for a school.
@@ -54,6 +70,18 @@ dyn_clone::clone_trait_object!(Credentials);
 
 impl<T> Credentials for T where T: Downcast + Send + Sync + DynClone {}
 
+/// The school no longer accepts the stored credential, so the user has to log in again.
+#[derive(Debug)]
+pub struct CredentialExpired;
+
+impl Display for CredentialExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Login credential expired, please login again")
+    }
+}
+
+impl std::error::Error for CredentialExpired {}
+
 /// A login session for the user to login.
 ///
 /// The typical workflow is:
@@ -92,4 +120,75 @@ pub trait LoginSession: Send + Sync + Debug {
 pub trait CalendarHelper {
     /// The name of the school.
     fn school_name(&self) -> &str;
+
+    /// The time zone the school's timetable is in.
+    ///
+    /// Events are written in this time zone, so that they show up at the right time
+    /// even when students are abroad.
+    fn timezone(&self) -> SchoolTimeZone {
+        SchoolTimeZone::ASIA_SHANGHAI
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A school that can't log in, for testing what's done with its courses.
+    #[derive(Debug)]
+    pub(crate) struct TestSchool;
+
+    #[async_trait]
+    impl School for TestSchool {
+        async fn new(_db: Arc<Mutex<SqlitePool>>) -> Result<Self> {
+            Ok(Self)
+        }
+
+        fn adapter_name(&self) -> &str {
+            "测试"
+        }
+    }
+
+    #[async_trait]
+    impl Login for TestSchool {
+        async fn new_login_session(&self) -> Result<Box<dyn LoginSession>> {
+            unimplemented!()
+        }
+
+        async fn get_cred_from_db(&self, _db_key: &str) -> Option<Box<dyn Credentials>> {
+            None
+        }
+
+        async fn delete_cred_from_db(&self, _db_key: &str) -> Result<bool> {
+            Ok(false)
+        }
+
+        async fn purge_unused_creds(&self, _unused_since: chrono::NaiveDateTime) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn create_authenticated_client(
+            &self,
+            _credentials: Box<dyn Credentials>,
+        ) -> Result<ClientWithMiddleware> {
+            unimplemented!()
+        }
+
+        async fn mark_cred_stale(&self, _db_key: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl CoursesProvider for TestSchool {
+        async fn courses(&self, _client: &ClientWithMiddleware) -> Result<Timetable> {
+            Ok(Timetable::default())
+        }
+    }
+
+    impl CalendarHelper for TestSchool {
+        fn school_name(&self) -> &str {
+            "测试大学"
+        }
+    }
 }
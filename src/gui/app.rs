@@ -1,4 +1,5 @@
 use super::steps::login::Login;
+use super::steps::revoke::Revoke;
 use super::steps::select_school::SchoolAPISelect;
 use super::steps::view_link::ViewLink;
 use dioxus::prelude::*;
@@ -16,6 +17,8 @@ pub(super) enum Route {
     Login {},
     #[route("/view_link")]
     ViewLink,
+    #[route("/revoke")]
+    Revoke,
 }
 
 #[component]
@@ -109,6 +109,8 @@ fn InputField(
 
 #[cfg(feature = "server")]
 use crate::adapters::login_process::LoginProcess;
+#[cfg(feature = "server")]
+use crate::server::state::ServerState;
 
 #[get("/api/get_captcha", session: LoginProcess)]
 #[tracing::instrument(err, ret)]
@@ -125,14 +127,19 @@ async fn get_captcha() -> Result<Vec<u8>> {
     Ok(png_bytes)
 }
 
-#[post("/api/login", session: LoginProcess)]
-#[tracing::instrument(skip(password, captcha_answer), err)]
+#[post("/api/login", session: LoginProcess, state: ServerState)]
+#[tracing::instrument(skip(password, captcha_answer, state), err)]
 async fn login_for_session(
     username: String,
     password: String,
     captcha_answer: String,
 ) -> Result<String> {
+    use crate::plugins::PlugIn;
+
     let cred_db_key = session.login(username, password, captcha_answer).await?;
+    if let Some(school) = session.selected_school().await {
+        state.plugins.post_login(&*school, &cred_db_key).await?;
+    }
 
     Ok(cred_db_key)
 
@@ -1,5 +1,6 @@
-//! `steps` contains UI pages for login steps.
+//! `steps` contains UI pages for login steps, and for revoking the subscription afterwards.
 
 pub mod login;
+pub mod revoke;
 pub mod select_school;
 pub mod view_link;
@@ -0,0 +1,133 @@
+use super::super::utils::Hero;
+use dioxus::prelude::*;
+
+/// Delete the login credential behind a subscription link.
+#[component]
+pub fn Revoke() -> Element {
+    let mut link = use_signal(|| "".to_string());
+    let mut result = use_signal(|| None::<Result<(), String>>);
+
+    rsx! {
+        Hero {
+            image: "https://authserver.nju.edu.cn/authserver/njuTheme/customStatic/web/images/back3.jpg",
+
+            div {
+                class: "card bg-base-200 max-w-lg card-xl shadow-sm",
+
+                div {
+                    class: "card-body",
+
+                    h2 { class: "card-title", "撤销订阅" }
+                    p { "撤销后，服务器会删除为这个订阅保存的登录信息，该链接将不再更新。" }
+
+                    input {
+                        type: "text",
+                        class: "input w-full",
+                        placeholder: "粘贴你的订阅链接",
+                        oninput: move |event| {
+                            link.set(event.data().value());
+                        }
+                    }
+                    button {
+                        class: "btn btn-error",
+                        onclick: move |_event| async move {
+                            let revoked = revoke_subscription(link()).await;
+                            result.set(Some(revoked.map_err(|error| error.to_string())));
+                        },
+                        "撤销"
+                    }
+
+                    match result() {
+                        Some(Ok(())) => rsx! {
+                            p { class: "text-success", "已撤销，服务器上已不再保存你的登录信息。" }
+                        },
+                        Some(Err(error)) => rsx! {
+                            p { class: "text-error", "撤销失败：{error}" }
+                        },
+                        None => rsx! {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+use crate::server::export::Format;
+#[cfg(feature = "server")]
+use crate::server::state::ServerState;
+
+#[post("/api/revoke", state: ServerState)]
+#[tracing::instrument(skip(link), err)]
+async fn revoke_subscription(link: String) -> Result<()> {
+    let (adapter, key) = parse_link(&link).context("无法识别订阅链接")?;
+
+    let school = state
+        .school_adapters
+        .lock()
+        .await
+        .get(adapter.as_str())
+        .context("无法识别订阅链接")?
+        .clone();
+
+    school
+        .delete_cred_from_db(&key)
+        .await?
+        .then_some(())
+        .context("没有找到这个订阅，可能已经撤销过了")?;
+    state.cache.remove(school.adapter_name(), &key).await?;
+    state.revisions.remove(school.adapter_name(), &key).await?;
+
+    Ok(())
+}
+
+/// The adapter and key in a subscription link.
+///
+/// Links look like webcal://example.com/sub_dir/calendar/{adapter}/{key}/schedule.ics,
+/// maybe with another file like schedule.json, options like `?alarm=15`, or no scheme.
+#[cfg(feature = "server")]
+fn parse_link(link: &str) -> Option<(String, String)> {
+    let link = link.trim();
+    let link = reqwest::Url::parse(link)
+        .ok()
+        .filter(|url| !url.cannot_be_a_base())
+        .or_else(|| reqwest::Url::parse(&format!("https://{link}")).ok())?;
+
+    let mut segments = link
+        .path_segments()?
+        .rev()
+        .filter(|segment| !segment.is_empty())
+        .peekable();
+    segments.next_if(|file| Format::from_file_name(file).is_some());
+    let key = urlencoding::decode(segments.next()?).ok()?;
+    let adapter = urlencoding::decode(segments.next()?).ok()?;
+    Some((adapter.into_owned(), key.into_owned()))
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    fn parsed(adapter: &str, key: &str) -> Option<(String, String)> {
+        Some((adapter.to_string(), key.to_string()))
+    }
+
+    #[test]
+    fn links() {
+        let adapter = urlencoding::encode("南京大学本科生");
+        let expected = parsed("南京大学本科生", "abc");
+
+        for link in [
+            format!("webcal://example.com/sub/calendar/{adapter}/abc/schedule.ics"),
+            format!("https://example.com/calendar/{adapter}/abc/schedule.json"),
+            format!("webcal://example.com/calendar/{adapter}/abc/schedule.ics?alarm=15"),
+            format!("https://example.com/calendar/{adapter}/abc/schedule.ics#top"),
+            format!("https://example.com/calendar/{adapter}/abc/"),
+            format!("  example.com/calendar/南京大学本科生/abc/schedule.ics  "),
+            format!("localhost:8080/calendar/{adapter}/abc/schedule.ics"),
+        ] {
+            assert_eq!(parse_link(&link), expected, "{link}");
+        }
+        assert_eq!(parse_link("schedule.ics"), None);
+    }
+}
@@ -58,6 +58,11 @@ pub fn SchoolAPISelect() -> Element {
                     span { class: "loading loading-spinner" }
                 }
             }
+
+            p {
+                class: "mt-5",
+                Link { class: "link text-neutral-content", to: Route::Revoke, "撤销已有的订阅链接" }
+            }
         }
     }
 }
@@ -1,3 +1,4 @@
+use super::super::app::Route;
 use super::super::utils::Hero;
 use dioxus::prelude::*;
 use urlencoding::encode as url_encode;
@@ -75,6 +76,31 @@ pub fn ViewLink() -> Element {
                         title: "Linux",
                         p { "GNOME自带的日历就可以添加订阅" }
                     }
+                    Howto {
+                        title: "定制订阅内容",
+                        p { "可以在链接末尾加上参数，比如" code { "schedule.ics?exams=only&alarm=15" } "。多个参数之间用" code { "&" } "连接。" }
+                        ul {
+                            class: "list-disc list-inside text-left",
+                            li { code { "exams=only" } "只要考试，" code { "exams=none" } "不要考试" }
+                            li { code { "alarm=15" } "每个日程前15分钟提醒，多个提醒用逗号分隔，如" code { "1d,1h" } }
+                            li { code { "alarm.lecture=15m" } "、" code { "alarm.exam=1d,1h" } "分别设置课程和考试的提醒" }
+                            li { code { "notes=minimal" } "备注中只保留校区，不含教师等信息" }
+                            li { code { "geo=false" } "不附带地图坐标" }
+                            li { code { "exclude=课程名" } "隐藏某门课，可以写多次" }
+                            li { code { "week=title" } "在标题前显示第几周，" code { "week=notes" } "在备注中显示" }
+                            li { code { "week_events=true" } "每周一添加“第N周”全天日程" }
+                            li { code { "plugin.holidays=false" } "不处理节假日和调休" }
+                            li { code { "client=coloros" } "按某个日历app的方式生成日历，一般会自动判断，地图不显示时可以试试" }
+                        }
+                    }
+                    Howto {
+                        title: "不想再使用？",
+                        p {
+                            "可以在"
+                            Link { class: "link link-accent", to: Route::Revoke, "撤销订阅" }
+                            "页面删除服务器上保存的登录信息，之后此链接将失效。"
+                        }
+                    }
                 }
             }
         }
@@ -2,6 +2,8 @@
 ///
 /// 要适配一个新学校，你需要新建一个struct，然后为其实现[`adapters::traits::School`]，
 /// 最后在[`server::state::ServerState::from_config`]中把它加上。
+/// 如果需要数据库表，在`migrations/`下为它新建一个目录存放迁移脚本，
+/// 并在[`adapters::traits::School::new`]中用[`adapters::migrations::run`]运行。
 #[cfg(feature = "server")]
 pub mod adapters;
 
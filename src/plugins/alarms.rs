@@ -0,0 +1,65 @@
+//! 日程提醒插件
+
+use crate::adapters::course::Course;
+use crate::plugins::{PlugIn, RequestContext};
+use async_trait::async_trait;
+use chrono::TimeDelta;
+use ics::properties::{Description, Trigger};
+use ics::{Alarm, Event};
+
+/// Adds alarms the subscriber asked for, see [`crate::server::options::AlarmOptions`].
+#[derive(Debug)]
+pub struct AlarmPlugin;
+
+/// The `TRIGGER` value for an alarm `lead` before the event starts, e.g. `-P1DT2H`.
+fn trigger_before(lead: &TimeDelta) -> String {
+    let days = lead.num_days();
+    let hours = lead.num_hours() % 24;
+    let minutes = lead.num_minutes() % 60;
+
+    let mut trigger = "-P".to_string();
+    if days != 0 {
+        trigger += &format!("{days}D");
+    }
+    if hours != 0 || minutes != 0 || days == 0 {
+        trigger += "T";
+        if hours != 0 {
+            trigger += &format!("{hours}H");
+        }
+        if minutes != 0 || hours == 0 {
+            trigger += &format!("{minutes}M");
+        }
+    }
+    trigger
+}
+
+#[async_trait]
+impl PlugIn for AlarmPlugin {
+    fn on_event(&self, ctx: &RequestContext<'_>, course: &Course, event: &mut Event<'_>) {
+        for lead in ctx.options.alarms.for_course(course) {
+            event.add_alarm(Alarm::display(
+                Trigger::new(trigger_before(lead)),
+                Description::new(course.name.clone()),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers() {
+        assert_eq!(trigger_before(&TimeDelta::zero()), "-PT0M");
+        assert_eq!(trigger_before(&TimeDelta::minutes(15)), "-PT15M");
+        assert_eq!(trigger_before(&TimeDelta::hours(1)), "-PT1H");
+        assert_eq!(trigger_before(&TimeDelta::minutes(90)), "-PT1H30M");
+        assert_eq!(trigger_before(&TimeDelta::days(1)), "-P1D");
+        assert_eq!(trigger_before(&TimeDelta::hours(26)), "-P1DT2H");
+        assert_eq!(
+            trigger_before(&(TimeDelta::days(2) + TimeDelta::minutes(5))),
+            "-P2DT5M"
+        );
+    }
+}
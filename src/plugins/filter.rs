@@ -0,0 +1,25 @@
+//! 按订阅选项筛选课程
+
+use crate::adapters::{course::Course, semester::Semester};
+use crate::plugins::{PlugIn, RequestContext};
+use async_trait::async_trait;
+
+/// Drops courses the subscriber doesn't want, see
+/// [`CalendarOptions::includes`](crate::server::options::CalendarOptions::includes).
+#[derive(Debug)]
+pub struct FilterPlugin;
+
+#[async_trait]
+impl PlugIn for FilterPlugin {
+    async fn pre_generate_calendar(
+        &self,
+        ctx: &RequestContext<'_>,
+        _semester: Option<&Semester>,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        courses
+            .into_iter()
+            .filter(|course| ctx.options.includes(course))
+            .collect()
+    }
+}
@@ -1,145 +0,0 @@
-//! 调休插件
-
-use crate::adapters::{course::Course, traits::School};
-use crate::plugins::PlugIn;
-use anyhow::Context;
-use anyhow::Result;
-use async_trait::async_trait;
-use chrono::{DateTime, NaiveDate, Utc};
-use reqwest_middleware::ClientWithMiddleware;
-use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
-use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
-use tracing::{Level, event, instrument};
-
-#[derive(Debug)]
-pub struct HolidayPlugin {
-    holidays: HashSet<NaiveDate>,
-    _compensate_days: HashSet<NaiveDate>,
-}
-
-fn build_client() -> Result<ClientWithMiddleware> {
-    let client = reqwest_middleware::ClientBuilder::new(
-        reqwest::Client::builder()
-            .user_agent("nju-schedule-ics")
-            .timeout(std::time::Duration::from_secs(10))
-            .build()?,
-    )
-    .with(RetryTransientMiddleware::new_with_policy(
-        ExponentialBackoff::builder().build_with_max_retries(3),
-    ))
-    .build();
-
-    Ok(client)
-}
-
-impl HolidayPlugin {
-    pub async fn new() -> Result<Self> {
-        let client = build_client()?;
-        let holidays = client
-            .get("https://www.shuyz.com/githubfiles/china-holiday-calender/master/holidayAPI.json")
-            .send()
-            .await
-            .context("Failed to fetch holiday info")?
-            .json::<ShuyzHolidayResponse>()
-            .await?;
-        event!(
-            Level::INFO,
-            holidays = tracing::field::debug(&holidays),
-            "Getting holidays from shuyz api"
-        );
-
-        let mut holiday_dates = HashSet::new();
-        let mut compensate_dates = HashSet::new();
-
-        for (_, year_holidays) in holidays.Years {
-            for holiday in year_holidays {
-                // Parse holiday dates
-                let start_date = NaiveDate::parse_from_str(&holiday.StartDate, "%Y-%m-%d")?;
-                let end_date = NaiveDate::parse_from_str(&holiday.EndDate, "%Y-%m-%d")?;
-
-                // Add all dates in the holiday range
-                let mut current_date = start_date;
-                while current_date <= end_date {
-                    holiday_dates.insert(current_date);
-                    current_date = current_date
-                        .succ_opt()
-                        .expect("Holiday is beyond the last representable day");
-                }
-
-                // Parse compensate days
-                for comp_day in &holiday.CompDays {
-                    let comp_date = NaiveDate::parse_from_str(comp_day, "%Y-%m-%d")?;
-                    compensate_dates.insert(comp_date);
-                }
-            }
-        }
-
-        Ok(HolidayPlugin {
-            holidays: holiday_dates,
-            _compensate_days: compensate_dates,
-        })
-    }
-
-    /// Check if a DateTime<Utc> falls on a holiday in UTC+8 timezone
-    #[instrument(ret)]
-    pub fn is_in_holiday(&self, datetime: &DateTime<Utc>) -> bool {
-        // Convert UTC to UTC+8
-        let offset = chrono::FixedOffset::east_opt(8 * 3600).expect("8 hours offset out of bound");
-        let utc_plus_8 = datetime.with_timezone(&offset);
-        let naive_date = utc_plus_8.date_naive();
-
-        // Check if the date is in holidays
-        self.holidays.contains(&naive_date)
-    }
-}
-
-#[async_trait]
-impl PlugIn for HolidayPlugin {
-    async fn pre_generate_calendar<'a, 'b, 'c>(
-        &self,
-        _school: &'a dyn School,
-        courses: Vec<Course>,
-    ) -> Vec<Course>
-    where
-        'b: 'c,
-    {
-        // Filter out courses that fall on holidays/
-        courses
-            .into_iter()
-            .map(|mut course| {
-                if course.name.contains("考试") {
-                    course
-                } else {
-                    // Filter out time slots that fall on holidays
-                    let filtered_times: Vec<(DateTime<Utc>, DateTime<Utc>)> = course
-                        .time
-                        .into_iter()
-                        .filter(|(start_time, _)| !self.is_in_holiday(start_time))
-                        .collect();
-
-                    // Update the course with filtered times
-                    course.time = filtered_times;
-                    course
-                }
-            })
-            .collect()
-    }
-}
-
-#[derive(Deserialize, Debug)]
-#[allow(non_snake_case, dead_code)]
-struct ShuyzHolidayResponse {
-    Name: String,
-    Timezone: String,
-    Years: HashMap<i32, Vec<ShuyzHoliday>>,
-}
-
-#[derive(Deserialize, Debug)]
-#[allow(non_snake_case, dead_code)]
-struct ShuyzHoliday {
-    Name: String,
-    StartDate: String,
-    EndDate: String,
-    CompDays: Vec<String>,
-}
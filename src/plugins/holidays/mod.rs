@@ -0,0 +1,433 @@
+//! 调休插件
+
+pub mod providers;
+
+use crate::adapters::{
+    course::{Course, CourseKind},
+    semester::Semester,
+    timezone::SchoolTimeZone,
+};
+use crate::plugins::{PlugIn, RequestContext};
+use anyhow::Result;
+use anyhow::bail;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Utc, Weekday};
+use providers::{
+    BundledProvider, CachedProvider, FileProvider, Holiday, HolidayProvider, ShuyzProvider,
+    apply_overrides,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{Level, event, instrument};
+
+/// `[plugins.holidays]` in `config.toml`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HolidayConfig {
+    /// Where to get holidays: `shuyz`, `bundled`, or path to a JSON or TOML file
+    pub source: String,
+    /// How often to get holidays from `source` again, in hours. 0 means only at startup.
+    pub refresh_hours: u64,
+    /// Holidays that replace overlapping ones from `source`, e.g. for school-specific changes
+    pub overrides: Vec<Holiday>,
+    /// Which timetable is followed on compensatory workdays (调休上班),
+    /// as given by official notices, e.g. `2025-10-11 = "周三"`
+    pub compensate_days: BTreeMap<NaiveDate, String>,
+}
+
+impl Default for HolidayConfig {
+    fn default() -> Self {
+        Self {
+            source: "shuyz".to_string(),
+            refresh_hours: 24,
+            overrides: vec![],
+            compensate_days: BTreeMap::new(),
+        }
+    }
+}
+
+/// Parse which timetable a compensatory workday follows, as given in config.
+///
+/// Either a date like `2025-10-08`, or a weekday like `wed` or `周三`, which means
+/// that weekday in the same week (Monday to Sunday) as `day`.
+fn parse_followed_day(day: NaiveDate, followed: &str) -> Result<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(followed, "%Y-%m-%d") {
+        return Ok(date);
+    }
+
+    let weekday = match followed {
+        "周一" => Weekday::Mon,
+        "周二" => Weekday::Tue,
+        "周三" => Weekday::Wed,
+        "周四" => Weekday::Thu,
+        "周五" => Weekday::Fri,
+        "周六" => Weekday::Sat,
+        "周日" => Weekday::Sun,
+        _ => match followed.parse() {
+            Ok(weekday) => weekday,
+            Err(_) => bail!(
+                "Invalid timetable for compensatory day {day}: {followed}, expected a date or a weekday"
+            ),
+        },
+    };
+
+    Ok(day.week(Weekday::Mon).first_day() + TimeDelta::days(weekday.num_days_from_monday() as i64))
+}
+
+/// e.g. `10月8日（周三）`
+fn format_day(date: &NaiveDate) -> String {
+    const WEEKDAYS: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+    format!(
+        "{}（周{}）",
+        date.format("%-m月%-d日"),
+        WEEKDAYS[date.weekday().num_days_from_monday() as usize]
+    )
+}
+
+/// Days off and compensatory workdays, from a list of holidays.
+#[derive(Debug, Default)]
+pub struct HolidayCalendar {
+    holidays: HashSet<NaiveDate>,
+    /// Compensatory workdays (调休上班), and the day whose timetable is followed on each.
+    compensate_days: BTreeMap<NaiveDate, NaiveDate>,
+}
+
+impl HolidayCalendar {
+    /// `followed` maps compensatory workdays to the day whose timetable is followed on them.
+    /// Those not given follow a weekday in the holiday, paired in order with the last ones.
+    pub fn new(holidays: &[Holiday], followed: &BTreeMap<NaiveDate, NaiveDate>) -> Self {
+        let mut holiday_dates = HashSet::new();
+        let mut compensate_dates = BTreeMap::new();
+
+        for holiday in holidays {
+            // Add all dates in the holiday range
+            holiday_dates.extend(
+                holiday
+                    .start
+                    .iter_days()
+                    .take_while(|date| *date <= holiday.end),
+            );
+
+            let mut comp_dates = holiday.compensate_days.clone();
+            comp_dates.sort();
+
+            // Without an official notice, guess that they make up for the last
+            // weekdays in this holiday
+            let holiday_weekdays: Vec<NaiveDate> = holiday
+                .start
+                .iter_days()
+                .take_while(|date| *date <= holiday.end)
+                .filter(|date| date.weekday().num_days_from_monday() < 5)
+                .collect();
+            let skip = holiday_weekdays.len().saturating_sub(comp_dates.len());
+            for (comp_date, followed) in comp_dates
+                .into_iter()
+                .zip(holiday_weekdays.into_iter().skip(skip))
+            {
+                compensate_dates.insert(comp_date, followed);
+            }
+        }
+
+        compensate_dates.extend(followed);
+
+        Self {
+            holidays: holiday_dates,
+            compensate_days: compensate_dates,
+        }
+    }
+
+    /// Check if a DateTime<Utc> falls on a holiday in the school's timezone
+    #[instrument(skip(self), ret)]
+    pub fn is_in_holiday(&self, datetime: &DateTime<Utc>, timezone: &SchoolTimeZone) -> bool {
+        let naive_date = datetime.with_timezone(&timezone.offset()).date_naive();
+
+        // Check if the date is in holidays
+        self.holidays.contains(&naive_date)
+    }
+
+    /// Occurrences of `course` on compensatory workdays, as a make-up course.
+    ///
+    /// Occurrences on the day whose timetable is followed are copied onto the
+    /// compensatory workday.
+    fn compensation_of(&self, course: &Course, timezone: &SchoolTimeZone) -> Option<Course> {
+        let mut time = vec![];
+        let mut notes = vec![];
+
+        for (day, followed) in &self.compensate_days {
+            let shift = *day - *followed;
+            let moved: Vec<_> = course
+                .time
+                .iter()
+                .filter(|(start, _)| {
+                    start.with_timezone(&timezone.offset()).date_naive() == *followed
+                })
+                .map(|(start, end)| (*start + shift, *end + shift))
+                .collect();
+
+            if !moved.is_empty() {
+                time.extend(moved);
+                notes.push(format!(
+                    "调休：{}按{}课表上课",
+                    format_day(day),
+                    format_day(followed)
+                ));
+            }
+        }
+
+        if time.is_empty() {
+            return None;
+        }
+
+        let mut compensation = course.clone();
+        compensation.name = format!("{}（调休）", course.name);
+        compensation.id = format!("{}/调休", course.id);
+        compensation.kind = CourseKind::Makeup;
+        compensation.time = time;
+        notes.extend(compensation.notes);
+        compensation.notes = notes;
+        Some(compensation)
+    }
+}
+
+#[derive(Debug)]
+pub struct HolidayPlugin {
+    source: Box<dyn HolidayProvider>,
+    /// Replace holidays from `source` that overlap them
+    overrides: Vec<Holiday>,
+    /// See [`HolidayCalendar::new`]
+    followed: BTreeMap<NaiveDate, NaiveDate>,
+    calendar: RwLock<Arc<HolidayCalendar>>,
+}
+
+impl HolidayPlugin {
+    /// Never fails because of an unreachable source: we fall back to cached holidays,
+    /// and then bundled ones.
+    pub async fn new(config: &HolidayConfig, db: Arc<Mutex<SqlitePool>>) -> Result<Self> {
+        let source: Box<dyn HolidayProvider> = match config.source.as_str() {
+            "bundled" => Box::new(BundledProvider),
+            "shuyz" => Box::new(CachedProvider::new(Box::new(ShuyzProvider::new()?), db)),
+            path => Box::new(CachedProvider::new(
+                Box::new(FileProvider { path: path.into() }),
+                db,
+            )),
+        };
+        let followed = config
+            .compensate_days
+            .iter()
+            .map(|(day, followed)| Ok((*day, parse_followed_day(*day, followed)?)))
+            .collect::<Result<_>>()?;
+
+        let plugin = Self {
+            source,
+            overrides: config.overrides.clone(),
+            followed,
+            calendar: RwLock::new(Arc::default()),
+        };
+
+        if let Err(error) = plugin.refresh().await {
+            event!(
+                Level::WARN,
+                error = format!("{error:?}"),
+                "Failed to get holidays, using bundled ones"
+            );
+            plugin.update(BundledProvider.holidays().await?);
+        }
+
+        Ok(plugin)
+    }
+
+    /// Get holidays from source again.
+    ///
+    /// On failure, holidays we already have are kept.
+    #[instrument(skip(self), err)]
+    pub async fn refresh(&self) -> Result<()> {
+        let holidays = self.source.holidays().await?;
+        self.update(holidays);
+        Ok(())
+    }
+
+    /// Refresh every `every` in background.
+    ///
+    /// `every` must not be zero.
+    pub fn spawn_refresh(self: Arc<Self>, every: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(Instant::now() + every, every);
+            loop {
+                interval.tick().await;
+                // Errors are logged by refresh
+                let _ = self.refresh().await;
+            }
+        });
+    }
+
+    fn update(&self, holidays: Vec<Holiday>) {
+        let holidays = apply_overrides(holidays, &self.overrides);
+        let calendar = HolidayCalendar::new(&holidays, &self.followed);
+        event!(
+            Level::INFO,
+            compensate_days = tracing::field::debug(&calendar.compensate_days),
+            "Updated holidays"
+        );
+
+        *self
+            .calendar
+            .write()
+            .expect("Holiday calendar lock poisoned") = Arc::new(calendar);
+    }
+
+    fn calendar(&self) -> Arc<HolidayCalendar> {
+        self.calendar
+            .read()
+            .expect("Holiday calendar lock poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl PlugIn for HolidayPlugin {
+    async fn pre_generate_calendar(
+        &self,
+        ctx: &RequestContext<'_>,
+        _semester: Option<&Semester>,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        let timezone = ctx.school.timezone();
+        let calendar = self.calendar();
+
+        // Filter out courses that fall on holidays, and add those on compensatory workdays
+        courses
+            .into_iter()
+            .flat_map(|mut course| {
+                if course.kind == CourseKind::Exam || course.all_day {
+                    vec![course]
+                } else {
+                    let compensation = calendar.compensation_of(&course, &timezone);
+
+                    // Filter out time slots that fall on holidays
+                    let filtered_times: Vec<(DateTime<Utc>, DateTime<Utc>)> = course
+                        .time
+                        .into_iter()
+                        .filter(|(start_time, _)| !calendar.is_in_holiday(start_time, &timezone))
+                        .collect();
+
+                    // Update the course with filtered times
+                    course.time = filtered_times;
+                    std::iter::once(course).chain(compensation).collect()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::course::tests::class;
+    use chrono::TimeZone;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// 2025 National Day and Mid-Autumn Festival
+    fn national_day() -> Holiday {
+        Holiday {
+            name: "国庆节、中秋节".to_string(),
+            start: date(2025, 10, 1),
+            end: date(2025, 10, 8),
+            compensate_days: vec![date(2025, 10, 11), date(2025, 9, 28)],
+        }
+    }
+
+    #[test]
+    fn followed_day() {
+        let saturday = date(2025, 10, 11);
+        assert_eq!(
+            parse_followed_day(saturday, "2025-10-08").unwrap(),
+            date(2025, 10, 8)
+        );
+        assert_eq!(
+            parse_followed_day(saturday, "周三").unwrap(),
+            date(2025, 10, 8)
+        );
+        assert_eq!(
+            parse_followed_day(saturday, "wed").unwrap(),
+            date(2025, 10, 8)
+        );
+        // Sunday is the last day of its week
+        assert_eq!(
+            parse_followed_day(date(2025, 9, 28), "周二").unwrap(),
+            date(2025, 9, 23)
+        );
+        assert!(parse_followed_day(saturday, "星期三").is_err());
+    }
+
+    #[test]
+    fn guess_last_weekdays_of_holiday() {
+        let calendar = HolidayCalendar::new(&[national_day()], &BTreeMap::new());
+        assert_eq!(
+            calendar.compensate_days,
+            BTreeMap::from([
+                (date(2025, 9, 28), date(2025, 10, 7)),
+                (date(2025, 10, 11), date(2025, 10, 8)),
+            ])
+        );
+        assert_eq!(calendar.holidays.len(), 8);
+    }
+
+    #[test]
+    fn config_overrides_guess() {
+        let followed = BTreeMap::from([(date(2025, 9, 28), date(2025, 10, 3))]);
+        let calendar = HolidayCalendar::new(&[national_day()], &followed);
+        assert_eq!(
+            calendar.compensate_days,
+            BTreeMap::from([
+                (date(2025, 9, 28), date(2025, 10, 3)),
+                (date(2025, 10, 11), date(2025, 10, 8)),
+            ])
+        );
+    }
+
+    #[test]
+    fn in_holiday_by_school_time() {
+        let calendar = HolidayCalendar::new(&[national_day()], &BTreeMap::new());
+        let timezone = SchoolTimeZone::ASIA_SHANGHAI;
+        // 2025-10-01 00:30 in Shanghai
+        let start = Utc.with_ymd_and_hms(2025, 9, 30, 16, 30, 0).unwrap();
+        assert!(calendar.is_in_holiday(&start, &timezone));
+        // 2025-10-09 00:30 in Shanghai
+        let after = Utc.with_ymd_and_hms(2025, 10, 8, 16, 30, 0).unwrap();
+        assert!(!calendar.is_in_holiday(&after, &timezone));
+    }
+
+    #[test]
+    fn compensation_follows_timetable() {
+        let calendar = HolidayCalendar::new(&[national_day()], &BTreeMap::new());
+        let timezone = SchoolTimeZone::ASIA_SHANGHAI;
+
+        // Tuesdays, so 2025-10-07 of week 6 is followed on 2025-09-28
+        let tuesday = class("高等数学01班", 1..=16, 1);
+        let compensation = calendar.compensation_of(&tuesday, &timezone).unwrap();
+        let start = Utc.with_ymd_and_hms(2025, 9, 28, 0, 0, 0).unwrap();
+        assert_eq!(
+            compensation.time,
+            vec![(start, start + TimeDelta::minutes(110))]
+        );
+        assert_eq!(compensation.name, "高等数学（调休）");
+        assert_eq!(compensation.id, "高等数学01班/调休");
+        assert_eq!(compensation.kind, CourseKind::Makeup);
+        assert_eq!(
+            compensation.notes,
+            vec!["调休：9月28日（周日）按10月7日（周二）课表上课"]
+        );
+
+        // Nothing on Mondays is followed
+        let monday = class("高等数学02班", 1..=16, 0);
+        assert!(calendar.compensation_of(&monday, &timezone).is_none());
+    }
+}
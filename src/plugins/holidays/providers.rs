@@ -0,0 +1,273 @@
+//! Where holidays come from.
+//!
+//! The shuyz API is kept up to date, but it may be unreachable. Its last response is kept
+//! in database by [`CachedProvider`], and a copy bundled into the binary
+//! ([`BundledProvider`]) is the last resort.
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use reqwest_middleware::ClientWithMiddleware;
+use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use sqlx::types::Json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{Level, event, instrument};
+
+/// A public holiday, with its compensatory workdays (调休上班).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holiday {
+    /// e.g. `国庆节、中秋节`
+    pub name: String,
+    /// First day off
+    pub start: NaiveDate,
+    /// Last day off
+    pub end: NaiveDate,
+    /// Weekends that are workdays to make up for this holiday
+    #[serde(default)]
+    pub compensate_days: Vec<NaiveDate>,
+}
+
+impl Holiday {
+    /// Whether the two holidays share any day.
+    pub fn overlaps(&self, other: &Holiday) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// Replace holidays that overlap any of `overrides` with them.
+pub fn apply_overrides(holidays: Vec<Holiday>, overrides: &[Holiday]) -> Vec<Holiday> {
+    holidays
+        .into_iter()
+        .filter(|holiday| !overrides.iter().any(|o| o.overlaps(holiday)))
+        .chain(overrides.iter().cloned())
+        .collect()
+}
+
+#[async_trait]
+pub trait HolidayProvider: Send + Sync + std::fmt::Debug {
+    /// Identifies this provider, e.g. in logs and as key of cached holidays
+    fn name(&self) -> String;
+
+    /// All known holidays.
+    async fn holidays(&self) -> Result<Vec<Holiday>>;
+}
+
+/// Format of holiday files, in JSON or TOML:
+///
+/// ```toml
+/// [[holidays]]
+/// name = "国庆节、中秋节"
+/// start = "2025-10-01"
+/// end = "2025-10-08"
+/// compensate_days = ["2025-09-28", "2025-10-11"]
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+struct HolidayFile {
+    holidays: Vec<Holiday>,
+}
+
+/// Holidays bundled into the binary, from `bundled.toml`.
+#[derive(Debug)]
+pub struct BundledProvider;
+
+#[async_trait]
+impl HolidayProvider for BundledProvider {
+    fn name(&self) -> String {
+        "bundled".to_string()
+    }
+
+    async fn holidays(&self) -> Result<Vec<Holiday>> {
+        let file: HolidayFile = toml::from_str(include_str!("bundled.toml"))
+            .context("Failed to parse bundled holidays")?;
+        Ok(file.holidays)
+    }
+}
+
+/// Holidays from a local JSON or TOML file, decided by extension.
+#[derive(Debug)]
+pub struct FileProvider {
+    pub path: PathBuf,
+}
+
+#[async_trait]
+impl HolidayProvider for FileProvider {
+    fn name(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    async fn holidays(&self) -> Result<Vec<Holiday>> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("Failed to read holidays from {}", self.path.display()))?;
+
+        let file: HolidayFile = match self.path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            Some("toml") => toml::from_str(&content)?,
+            _ => bail!(
+                "Unknown format of holiday file {}, expected .json or .toml",
+                self.path.display()
+            ),
+        };
+        Ok(file.holidays)
+    }
+}
+
+/// Holidays from https://github.com/shuyz/china-holiday-calender
+#[derive(Debug)]
+pub struct ShuyzProvider {
+    client: ClientWithMiddleware,
+}
+
+impl ShuyzProvider {
+    const URL: &str =
+        "https://www.shuyz.com/githubfiles/china-holiday-calender/master/holidayAPI.json";
+
+    pub fn new() -> Result<Self> {
+        let client = reqwest_middleware::ClientBuilder::new(
+            reqwest::Client::builder()
+                .user_agent("nju-schedule-ics")
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+        )
+        .with(RetryTransientMiddleware::new_with_policy(
+            ExponentialBackoff::builder().build_with_max_retries(3),
+        ))
+        .build();
+
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl HolidayProvider for ShuyzProvider {
+    fn name(&self) -> String {
+        "shuyz".to_string()
+    }
+
+    async fn holidays(&self) -> Result<Vec<Holiday>> {
+        let holidays = self
+            .client
+            .get(Self::URL)
+            .send()
+            .await
+            .context("Failed to fetch holiday info")?
+            .json::<ShuyzHolidayResponse>()
+            .await?;
+        event!(
+            Level::INFO,
+            holidays = tracing::field::debug(&holidays),
+            "Getting holidays from shuyz api"
+        );
+
+        let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d");
+        let mut result = vec![];
+        for (_, year_holidays) in holidays.Years {
+            for holiday in year_holidays {
+                result.push(Holiday {
+                    start: parse(&holiday.StartDate)?,
+                    end: parse(&holiday.EndDate)?,
+                    compensate_days: holiday
+                        .CompDays
+                        .iter()
+                        .map(|day| parse(day))
+                        .collect::<Result<_, _>>()?,
+                    name: holiday.Name,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Keeps the last holidays got from `inner` in database, and gives them when `inner` fails.
+#[derive(Debug)]
+pub struct CachedProvider {
+    inner: Box<dyn HolidayProvider>,
+    db: Arc<Mutex<SqlitePool>>,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Box<dyn HolidayProvider>, db: Arc<Mutex<SqlitePool>>) -> Self {
+        Self { inner, db }
+    }
+
+    async fn save(&self, holidays: &[Holiday]) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO holiday_cache (source, holidays, fetched_at) VALUES ($1, $2, $3)",
+        )
+        .bind(self.inner.name())
+        .bind(Json(holidays))
+        .bind(Utc::now())
+        .execute(&*self.db.lock().await)
+        .await?;
+        Ok(())
+    }
+
+    async fn load(&self) -> Result<Option<Vec<Holiday>>> {
+        let cached = sqlx::query_as::<_, (Json<Vec<Holiday>>,)>(
+            "SELECT holidays FROM holiday_cache WHERE source = ?",
+        )
+        .bind(self.inner.name())
+        .fetch_optional(&*self.db.lock().await)
+        .await?;
+        Ok(cached.map(|(Json(holidays),)| holidays))
+    }
+}
+
+#[async_trait]
+impl HolidayProvider for CachedProvider {
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    #[instrument(err)]
+    async fn holidays(&self) -> Result<Vec<Holiday>> {
+        match self.inner.holidays().await {
+            Ok(holidays) => {
+                if let Err(error) = self.save(&holidays).await {
+                    event!(
+                        Level::WARN,
+                        error = format!("{error:?}"),
+                        "Failed to cache holidays"
+                    );
+                }
+                Ok(holidays)
+            }
+            Err(error) => {
+                event!(
+                    Level::WARN,
+                    error = format!("{error:?}"),
+                    source = self.inner.name(),
+                    "Failed to get holidays, using cached ones"
+                );
+                match self.load().await? {
+                    Some(holidays) => Ok(holidays),
+                    None => Err(error.context("No cached holidays either")),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case, dead_code)]
+struct ShuyzHolidayResponse {
+    Name: String,
+    Timezone: String,
+    Years: HashMap<i32, Vec<ShuyzHoliday>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case, dead_code)]
+struct ShuyzHoliday {
+    Name: String,
+    StartDate: String,
+    EndDate: String,
+    CompDays: Vec<String>,
+}
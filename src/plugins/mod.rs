@@ -1,49 +1,383 @@
+use sqlx::SqlitePool;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
 
 use crate::{
-    adapters::{course::Course, traits::School},
-    plugins::holidays::HolidayPlugin,
+    adapters::{
+        course::Course,
+        semester::{Semester, Timetable},
+        traits::School,
+    },
+    plugins::{
+        alarms::AlarmPlugin,
+        filter::FilterPlugin,
+        holidays::{HolidayConfig, HolidayPlugin},
+        school_calendar::SchoolCalendarPlugin,
+        week_numbers::WeekNumberPlugin,
+    },
+    server::{config::PluginsConfig, options::CalendarOptions},
 };
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use async_trait::async_trait;
-use tracing::{info_span, instrument};
+use ics::{Event, ICalendar};
+use tracing::{Instrument, Level, event, info_span, instrument};
 
+pub mod alarms;
+pub mod filter;
 pub mod holidays;
+pub mod school_calendar;
+pub mod week_numbers;
 
+/// The calendar request that plugins run for.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestContext<'a> {
+    /// The school adapter of the subscription
+    pub school: &'a dyn School,
+    /// The subscription key, which identifies the user
+    pub key: &'a str,
+    /// What the subscriber asked for in the URL
+    pub options: &'a CalendarOptions,
+}
+
+/// Hooks into getting and generating calendars. All of them do nothing by default.
+///
+/// The order they are called for a calendar request is:
+/// [`PlugIn::post_fetch`] (only when we fetch from school instead of using cache),
+/// [`PlugIn::pre_generate_calendar`], [`PlugIn::on_event`] for each event, and
+/// [`PlugIn::post_render`].
 #[async_trait]
 pub trait PlugIn: Sync + Send {
-    /// After school adapter has got all the courses, before creating the calendar file.
+    /// After a user has logged in, with the key of the new subscription.
+    async fn post_login(&self, _school: &dyn School, _key: &str) -> Result<()> {
+        Ok(())
+    }
 
-    async fn pre_generate_calendar<'a, 'b, 'c>(
+    /// After school adapter has fetched the timetable, before it's cached.
+    ///
+    /// The result is shared by every variant of the subscription (see
+    /// [`crate::server::cache`]), so it shouldn't depend on `ctx.options`, and it runs
+    /// regardless of `?plugin.<name>=`.
+    async fn post_fetch(&self, _ctx: &RequestContext<'_>, timetable: Timetable) -> Timetable {
+        timetable
+    }
+
+    /// Before creating the calendar file from courses.
+    ///
+    /// `semester` is the current one, if the school tells.
+    async fn pre_generate_calendar(
         &self,
-        _school: &'a dyn School,
+        _ctx: &RequestContext<'_>,
+        _semester: Option<&Semester>,
         courses: Vec<Course>,
-    ) -> Vec<Course>
-    where
-        'b: 'c,
-    {
+    ) -> Vec<Course> {
         courses
     }
+
+    /// Change an event generated from `course`.
+    fn on_event(&self, _ctx: &RequestContext<'_>, _course: &Course, _event: &mut Event<'_>) {}
+
+    /// After all events are in the calendar, before it's written to file.
+    fn post_render(&self, _ctx: &RequestContext<'_>, _calendar: &mut ICalendar<'_>) {}
+}
+
+/// Names of all plugins, in the default order.
+///
+/// `school_calendar` runs after `holidays`, so that make-up classes on days the school
+/// cancels are dropped too.
+pub const PLUGINS: [&str; 5] = [
+    "holidays",
+    "school_calendar",
+    "filter",
+    "week_numbers",
+    "alarms",
+];
+
+/// Create a plugin by name, from its config table in `config.toml`.
+async fn create_plugin(
+    name: &str,
+    config: toml::Table,
+    db: Arc<Mutex<SqlitePool>>,
+) -> Result<Arc<dyn PlugIn>> {
+    let plugin: Arc<dyn PlugIn> = match name {
+        "school_calendar" => Arc::new(SchoolCalendarPlugin::new(&config.try_into()?)?),
+        "filter" => Arc::new(FilterPlugin),
+        "holidays" => {
+            let config: HolidayConfig = config.try_into()?;
+            let holidays = Arc::new(HolidayPlugin::new(&config, db).await?);
+            if config.refresh_hours > 0 {
+                holidays
+                    .clone()
+                    .spawn_refresh(Duration::from_secs(config.refresh_hours * 60 * 60));
+            }
+            holidays
+        }
+        "week_numbers" => Arc::new(WeekNumberPlugin),
+        "alarms" => Arc::new(AlarmPlugin),
+        _ => bail!("Unknown plugin {name}, expected one of {PLUGINS:?}"),
+    };
+    Ok(plugin)
+}
+
+/// A plugin in the [`Pipeline`].
+struct Stage {
+    name: &'static str,
+    plugin: Arc<dyn PlugIn>,
+    /// Whether to run when the subscriber doesn't say, see [`CalendarOptions::plugins`]
+    enabled: bool,
+    /// Adapters this never runs for
+    disabled_for: HashSet<String>,
+}
+
+/// Plugins to run for each calendar, in order.
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Plugins that run for calendars of `school`, with `options` if it's a calendar request.
+    fn stages<'a>(
+        &'a self,
+        school: &'a dyn School,
+        options: Option<&'a CalendarOptions>,
+    ) -> impl Iterator<Item = &'a Stage> {
+        self.stages.iter().filter(move |stage| {
+            !stage.disabled_for.contains(school.adapter_name())
+                && options
+                    .and_then(|options| options.plugins.get(stage.name))
+                    .copied()
+                    .unwrap_or(stage.enabled)
+        })
+    }
+
+    /// Warn about `?plugin.<name>=` in `options` that has no effect for `school`.
+    pub fn warn_unused_options(&self, school: &dyn School, options: &CalendarOptions) {
+        for name in options.plugins.keys() {
+            let stage = self.stages.iter().find(|stage| stage.name == *name);
+            if stage.is_none_or(|stage| stage.disabled_for.contains(school.adapter_name())) {
+                event!(
+                    Level::WARN,
+                    plugin = name,
+                    adapter = school.adapter_name(),
+                    "`?plugin.{name}=` is ignored, since the plugin isn't in the pipeline for this adapter"
+                );
+            }
+        }
+    }
 }
 
 #[async_trait]
-impl PlugIn for Vec<Arc<dyn PlugIn>> {
-    async fn pre_generate_calendar<'a, 'b, 'c>(
+impl PlugIn for Pipeline {
+    /// A failing plugin doesn't fail the login, it's only logged.
+    async fn post_login(&self, school: &dyn School, key: &str) -> Result<()> {
+        for stage in self.stages(school, None) {
+            if let Err(error) = stage
+                .plugin
+                .post_login(school, key)
+                .instrument(info_span!(
+                    "Running plugin after login",
+                    plugin = stage.name
+                ))
+                .await
+            {
+                event!(
+                    Level::ERROR,
+                    error = format!("{error:?}"),
+                    plugin = stage.name,
+                    "Plugin failed after login"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn post_fetch(&self, ctx: &RequestContext<'_>, timetable: Timetable) -> Timetable {
+        let mut result = timetable;
+        for stage in self.stages(ctx.school, None) {
+            result = stage
+                .plugin
+                .post_fetch(ctx, result)
+                .instrument(info_span!(
+                    "Running plugin after fetch",
+                    plugin = stage.name
+                ))
+                .await;
+        }
+        result
+    }
+
+    async fn pre_generate_calendar(
         &self,
-        school: &'a dyn School,
+        ctx: &RequestContext<'_>,
+        semester: Option<&Semester>,
         courses: Vec<Course>,
-    ) -> Vec<Course>
-    where
-        'b: 'c,
-    {
+    ) -> Vec<Course> {
         let mut result = courses;
-        for plugin in self {
-            result = plugin.pre_generate_calendar(school, result).await;
+        for stage in self.stages(ctx.school, Some(ctx.options)) {
+            result = stage
+                .plugin
+                .pre_generate_calendar(ctx, semester, result)
+                .instrument(info_span!("Running plugin", plugin = stage.name))
+                .await;
         }
         result
     }
+
+    fn on_event(&self, ctx: &RequestContext<'_>, course: &Course, event: &mut Event<'_>) {
+        for stage in self.stages(ctx.school, Some(ctx.options)) {
+            stage.plugin.on_event(ctx, course, event);
+        }
+    }
+
+    fn post_render(&self, ctx: &RequestContext<'_>, calendar: &mut ICalendar<'_>) {
+        for stage in self.stages(ctx.school, Some(ctx.options)) {
+            stage.plugin.post_render(ctx, calendar);
+        }
+    }
 }
 
-pub async fn get_plugins() -> Result<Vec<Arc<dyn PlugIn>>> {
-    Ok(vec![Arc::new(HolidayPlugin::new().await?)])
+/// Create plugins as configured in `[plugins]` of `config.toml`.
+///
+/// Plugins missing from `order` run after those in it, in the order of [`PLUGINS`].
+/// `adapters` are names of school adapters, which `[plugins.disabled]` is checked against.
+#[instrument(skip_all, err)]
+pub async fn get_plugins(
+    config: &PluginsConfig,
+    adapters: &[&str],
+    db: Arc<Mutex<SqlitePool>>,
+) -> Result<Pipeline> {
+    for (adapter, disabled) in &config.disabled {
+        if !adapters.contains(&adapter.as_str()) {
+            event!(
+                Level::WARN,
+                adapter,
+                "Unknown adapter {adapter} in [plugins.disabled], expected one of {adapters:?}"
+            );
+        }
+        for plugin in disabled {
+            if !PLUGINS.contains(&plugin.as_str()) {
+                event!(
+                    Level::WARN,
+                    adapter,
+                    plugin,
+                    "Unknown plugin {plugin} in [plugins.disabled], expected one of {PLUGINS:?}"
+                );
+            }
+        }
+    }
+
+    let mut order = vec![];
+    for name in &config.order {
+        let name = PLUGINS
+            .into_iter()
+            .find(|known| known == name)
+            .with_context(|| format!("Unknown plugin {name}, expected one of {PLUGINS:?}"))?;
+        order.push(name);
+    }
+    // Configs written before a plugin existed don't list it, and it shouldn't silently
+    // go missing. Deployers turn plugins off with `enabled` or `[plugins.disabled]`.
+    for name in PLUGINS {
+        if !order.contains(&name) {
+            event!(
+                Level::WARN,
+                plugin = name,
+                "Plugin {name} isn't in [plugins].order, running it after the others"
+            );
+            order.push(name);
+        }
+    }
+
+    let mut stages = vec![];
+    for name in order {
+        let mut plugin_config = config.configs.get(name).cloned().unwrap_or_default();
+        let enabled = match plugin_config.remove("enabled") {
+            Some(enabled) => enabled
+                .as_bool()
+                .with_context(|| format!("`enabled` of plugin {name} should be true or false"))?,
+            None => true,
+        };
+        let plugin = create_plugin(name, plugin_config, db.clone())
+            .await
+            .with_context(|| format!("Failed to create plugin {name}"))?;
+
+        stages.push(Stage {
+            name,
+            plugin,
+            enabled,
+            disabled_for: config
+                .disabled
+                .iter()
+                .filter(|(_, disabled)| disabled.iter().any(|plugin| plugin == name))
+                .map(|(adapter, _)| adapter.clone())
+                .collect(),
+        });
+    }
+
+    Ok(Pipeline { stages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::{
+        course::{CourseKind, tests::class},
+        traits::tests::TestSchool,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn no_make_up_classes_on_days_off() {
+        // The school cancels classes on 2025-09-28, a compensatory workday of National Day
+        let path = std::env::temp_dir().join("nju-schedule-ics-days-off.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[semesters]]
+            id = "2025-2026-1"
+            start = "2025-09-01"
+
+            [[semesters.days_off]]
+            name = "校运会"
+            start = "2025-09-28"
+            "#,
+        )
+        .unwrap();
+
+        let mut config = PluginsConfig::default();
+        config.configs.insert(
+            "holidays".to_string(),
+            toml::toml! { source = "bundled" refresh_hours = 0 },
+        );
+        config.configs.insert(
+            "school_calendar".to_string(),
+            toml::toml! { path = (path.to_str().unwrap()) },
+        );
+        let db = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let pipeline = get_plugins(&config, &["测试"], Arc::new(Mutex::new(db)))
+            .await
+            .unwrap();
+
+        let options = CalendarOptions::default();
+        let ctx = RequestContext {
+            school: &TestSchool,
+            key: "key",
+            options: &options,
+        };
+        // Tuesdays, including 2025-10-07 whose timetable is followed on 2025-09-28
+        let tuesday = class("高等数学01班", 1..=16, 1);
+        let courses = pipeline
+            .pre_generate_calendar(&ctx, None, vec![tuesday])
+            .await;
+
+        // The make-up class is only on 2025-09-28
+        let make_up = courses
+            .iter()
+            .find(|course| course.kind == CourseKind::Makeup)
+            .unwrap();
+        assert!(make_up.time.is_empty());
+    }
 }
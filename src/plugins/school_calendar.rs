@@ -0,0 +1,387 @@
+//! 校历插件
+//!
+//! 学校自己的停课安排（校运会、校庆、考试周等）国家法定节假日里没有，需要部署者在校历文件中写明。
+//! 学期的开始、结束日期由适配器从学校获取，也可以在校历文件中覆盖。
+//!
+//! 校历文件格式：
+//!
+//! ```toml
+//! [[semesters]]
+//! id = "2025-2026-1"
+//! # 可选，覆盖学校给出的日期
+//! # start = "2025-09-01"
+//! end = "2026-01-18"
+//! # 考试周停课
+//! exam_weeks = [17, 18]
+//!
+//! [[semesters.days_off]]
+//! name = "校运会"
+//! start = "2025-10-24"
+//! # 可选，默认与start相同
+//! # end = "2025-10-24"
+//! ```
+
+use crate::adapters::{
+    course::{Course, CourseKind},
+    semester::Semester,
+    timezone::SchoolTimeZone,
+};
+use crate::plugins::{PlugIn, RequestContext};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeDelta};
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// `[plugins.school_calendar]` in `config.toml`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SchoolCalendarConfig {
+    /// Path to the 校历 file
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchoolCalendarFile {
+    #[serde(default)]
+    semesters: Vec<SemesterCalendar>,
+}
+
+/// 校历 of one semester.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SemesterCalendar {
+    /// Same as [`Semester::id`]
+    pub id: String,
+    /// Monday of the first week, overrides what the school tells
+    pub start: Option<NaiveDate>,
+    /// The last day, overrides what the school tells
+    pub end: Option<NaiveDate>,
+    /// Weeks (1-based) with exams only
+    #[serde(default)]
+    pub exam_weeks: Vec<u32>,
+    /// Days the school cancels classes
+    #[serde(default)]
+    pub days_off: Vec<DayOff>,
+}
+
+/// Days the school cancels classes, e.g. for sports meets.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DayOff {
+    /// e.g. `校运会`
+    pub name: String,
+    /// First day off
+    pub start: NaiveDate,
+    /// Last day off, the same as `start` if not given
+    pub end: Option<NaiveDate>,
+}
+
+impl DayOff {
+    fn end(&self) -> NaiveDate {
+        self.end.unwrap_or(self.start)
+    }
+}
+
+/// Drops classes cancelled by the school, and adds all-day events like `第1周` and `考试周`.
+#[derive(Debug, Default)]
+pub struct SchoolCalendarPlugin {
+    semesters: Vec<SemesterCalendar>,
+}
+
+impl SchoolCalendarPlugin {
+    /// Read 校历 from the configured path. Without it, only what the school tells is used.
+    pub fn new(config: &SchoolCalendarConfig) -> Result<Self> {
+        let Some(path) = &config.path else {
+            return Ok(Self::default());
+        };
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read school calendar {path}"))?;
+        let file: SchoolCalendarFile = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse school calendar {path}"))?;
+
+        Ok(Self {
+            semesters: file.semesters,
+        })
+    }
+}
+
+/// An all-day 校历 event from `first` to `last`, both inclusive.
+fn all_day_event(
+    semester: &str,
+    id: String,
+    name: String,
+    first: NaiveDate,
+    last: NaiveDate,
+    timezone: &SchoolTimeZone,
+) -> Course {
+    Course::all_day(
+        semester,
+        id,
+        name,
+        CourseKind::Custom("校历".to_string()),
+        (first, last),
+        timezone,
+    )
+}
+
+/// Group sorted weeks into consecutive runs, e.g. `[17, 18, 20]` into `[(17, 18), (20, 20)]`.
+fn week_ranges(weeks: &[u32]) -> Vec<(u32, u32)> {
+    let mut weeks = weeks.to_vec();
+    weeks.sort();
+    weeks.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for week in weeks {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == week => *last = week,
+            _ => ranges.push((week, week)),
+        }
+    }
+    ranges
+}
+
+#[async_trait]
+impl PlugIn for SchoolCalendarPlugin {
+    async fn pre_generate_calendar(
+        &self,
+        ctx: &RequestContext<'_>,
+        semester: Option<&Semester>,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        let Some(semester_id) = semester
+            .map(|semester| semester.id.clone())
+            .or_else(|| courses.first().map(|course| course.semester.clone()))
+        else {
+            return courses;
+        };
+        let calendar = self
+            .semesters
+            .iter()
+            .find(|calendar| calendar.id == semester_id);
+        let timezone = ctx.school.timezone();
+
+        // What the deployer wrote takes precedence over what the school tells
+        let start = calendar
+            .and_then(|calendar| calendar.start)
+            .or(semester.map(|semester| semester.start));
+        let end = calendar
+            .and_then(|calendar| calendar.end)
+            .or(semester.and_then(|semester| semester.end));
+        let semester = start.map(|start| Semester {
+            id: semester_id.clone(),
+            start,
+            end,
+        });
+
+        let mut events = vec![];
+        let mut cancelled = HashSet::new();
+
+        if let Some(semester) = &semester {
+            let first_week = semester.monday_of(1);
+            events.push(all_day_event(
+                &semester_id,
+                "校历/第1周".to_string(),
+                "第1周".to_string(),
+                first_week,
+                first_week,
+                &timezone,
+            ));
+
+            let exam_weeks = calendar.map_or(&[][..], |calendar| &calendar.exam_weeks);
+            for (first, last) in week_ranges(exam_weeks) {
+                let first_day = semester.monday_of(first);
+                let last_day = semester.monday_of(last) + TimeDelta::days(6);
+                cancelled.extend(first_day.iter_days().take_while(|day| *day <= last_day));
+                events.push(all_day_event(
+                    &semester_id,
+                    format!("校历/考试周/{first}"),
+                    "考试周".to_string(),
+                    first_day,
+                    last_day,
+                    &timezone,
+                ));
+            }
+        }
+
+        if let Some(end) = end {
+            events.push(all_day_event(
+                &semester_id,
+                "校历/学期结束".to_string(),
+                "学期结束".to_string(),
+                end,
+                end,
+                &timezone,
+            ));
+        }
+
+        for day_off in calendar.map_or(&[][..], |calendar| &calendar.days_off) {
+            cancelled.extend(
+                day_off
+                    .start
+                    .iter_days()
+                    .take_while(|day| *day <= day_off.end()),
+            );
+            events.push(all_day_event(
+                &semester_id,
+                format!("校历/停课/{}", day_off.start),
+                format!("{}（停课）", day_off.name),
+                day_off.start,
+                day_off.end(),
+                &timezone,
+            ));
+        }
+
+        courses
+            .into_iter()
+            .map(|mut course| {
+                if course.kind != CourseKind::Exam && !course.all_day {
+                    course.time.retain(|(start, _)| {
+                        !cancelled.contains(&start.with_timezone(&timezone.offset()).date_naive())
+                    });
+                }
+                course
+            })
+            .chain(events)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::course::tests::class;
+    use crate::adapters::traits::tests::TestSchool;
+    use crate::server::options::CalendarOptions;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Run the plugin with `calendar` on `courses` of a semester from 2025-09-01 to 2026-01-18.
+    async fn run(
+        calendar: SemesterCalendar,
+        semester: Option<&Semester>,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        let plugin = SchoolCalendarPlugin {
+            semesters: vec![calendar],
+        };
+        let options = CalendarOptions::default();
+        let ctx = RequestContext {
+            school: &TestSchool,
+            key: "key",
+            options: &options,
+        };
+        plugin.pre_generate_calendar(&ctx, semester, courses).await
+    }
+
+    fn semester() -> Semester {
+        Semester {
+            id: "2025-2026-1".to_string(),
+            start: date(2025, 9, 1),
+            end: Some(date(2026, 1, 18)),
+        }
+    }
+
+    /// First and last day of an all-day event with `id` in `courses`.
+    fn days_of(courses: &[Course], id: &str) -> (NaiveDate, NaiveDate) {
+        let course = courses
+            .iter()
+            .find(|course| course.id == id)
+            .unwrap_or_else(|| panic!("No {id}"));
+        let offset = SchoolTimeZone::ASIA_SHANGHAI.offset();
+        let (start, end) = course.time[0];
+        (
+            start.with_timezone(&offset).date_naive(),
+            end.with_timezone(&offset).date_naive() - TimeDelta::days(1),
+        )
+    }
+
+    #[test]
+    fn ranges_of_weeks() {
+        assert_eq!(week_ranges(&[20, 17, 18, 18]), vec![(17, 18), (20, 20)]);
+        assert_eq!(week_ranges(&[]), vec![]);
+    }
+
+    #[tokio::test]
+    async fn cancels_exam_weeks_and_days_off() {
+        let calendar = SemesterCalendar {
+            id: "2025-2026-1".to_string(),
+            start: None,
+            end: None,
+            exam_weeks: vec![17, 18],
+            days_off: vec![DayOff {
+                name: "校运会".to_string(),
+                start: date(2025, 10, 24),
+                end: None,
+            }],
+        };
+        // Fridays, and an exam in week 17
+        let friday = class("高等数学01班", 1..=18, 4);
+        let mut exam = class("高等数学期末考试/2025-12-26", [17], 4);
+        exam.kind = CourseKind::Exam;
+
+        let courses = run(calendar, Some(&semester()), vec![friday, exam]).await;
+
+        let friday = &courses[0];
+        assert_eq!(friday.time.len(), 15);
+        let offset = SchoolTimeZone::ASIA_SHANGHAI.offset();
+        let days: Vec<_> = friday
+            .time
+            .iter()
+            .map(|(start, _)| start.with_timezone(&offset).date_naive())
+            .collect();
+        assert!(!days.contains(&date(2025, 10, 24)));
+        assert!(days.iter().all(|day| *day < date(2025, 12, 22)));
+        assert_eq!(courses[1].time.len(), 1);
+
+        assert_eq!(
+            days_of(&courses, "校历/第1周"),
+            (date(2025, 9, 1), date(2025, 9, 1))
+        );
+        assert_eq!(
+            days_of(&courses, "校历/考试周/17"),
+            (date(2025, 12, 22), date(2026, 1, 4))
+        );
+        assert_eq!(
+            days_of(&courses, "校历/停课/2025-10-24"),
+            (date(2025, 10, 24), date(2025, 10, 24))
+        );
+        assert_eq!(
+            days_of(&courses, "校历/学期结束"),
+            (date(2026, 1, 18), date(2026, 1, 18))
+        );
+    }
+
+    #[tokio::test]
+    async fn overrides_start_and_end() {
+        let calendar = SemesterCalendar {
+            id: "2025-2026-1".to_string(),
+            start: Some(date(2025, 9, 8)),
+            end: Some(date(2026, 1, 25)),
+            exam_weeks: vec![17],
+            days_off: vec![],
+        };
+
+        let courses = run(calendar.clone(), Some(&semester()), vec![]).await;
+        assert_eq!(
+            days_of(&courses, "校历/第1周"),
+            (date(2025, 9, 8), date(2025, 9, 8))
+        );
+        assert_eq!(
+            days_of(&courses, "校历/考试周/17"),
+            (date(2025, 12, 29), date(2026, 1, 4))
+        );
+        assert_eq!(
+            days_of(&courses, "校历/学期结束"),
+            (date(2026, 1, 25), date(2026, 1, 25))
+        );
+
+        // The school doesn't tell the semester, so it's found from courses
+        let courses = run(calendar, None, vec![class("高等数学01班", [1], 0)]).await;
+        assert_eq!(
+            days_of(&courses, "校历/第1周"),
+            (date(2025, 9, 8), date(2025, 9, 8))
+        );
+    }
+}
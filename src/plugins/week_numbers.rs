@@ -0,0 +1,239 @@
+//! 周数插件
+
+use crate::adapters::{
+    course::{Course, CourseKind},
+    semester::Semester,
+    traits::School,
+};
+use crate::plugins::{PlugIn, RequestContext};
+use crate::server::options::WeekNumbers;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashSet};
+
+/// Shows the teaching week of events, and marks the start of each week, as the subscriber asks
+/// (see `?week=` and `?week_events=` in [`crate::server::options::CalendarOptions`]).
+#[derive(Debug)]
+pub struct WeekNumberPlugin;
+
+impl WeekNumberPlugin {
+    /// Split `course` into one course per week, each with its week number.
+    ///
+    /// Occurrences of a series have different week numbers, so they can't share an event.
+    fn split_by_week(
+        course: Course,
+        semester: &Semester,
+        school: &dyn School,
+        week_numbers: WeekNumbers,
+    ) -> Vec<Course> {
+        let timezone = school.timezone();
+        let mut weeks = BTreeMap::<u32, Vec<_>>::new();
+        let mut before_semester = vec![];
+        for time in &course.time {
+            match semester.week_of(time.0.with_timezone(&timezone.offset()).date_naive()) {
+                Some(week) => weeks.entry(week).or_default().push(*time),
+                None => before_semester.push(*time),
+            }
+        }
+
+        let mut result: Vec<_> = weeks
+            .into_iter()
+            .map(|(week, time)| {
+                let mut weekly = course.clone();
+                weekly.id = format!("{}/第{}周", course.id, week);
+                weekly.time = time;
+                match week_numbers {
+                    WeekNumbers::Title => weekly.name = format!("[第{}周] {}", week, course.name),
+                    WeekNumbers::Notes => weekly.notes.insert(0, format!("第{week}周")),
+                    WeekNumbers::None => {}
+                }
+                weekly
+            })
+            .collect();
+
+        if !before_semester.is_empty() {
+            result.push(Course {
+                time: before_semester,
+                ..course
+            });
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl PlugIn for WeekNumberPlugin {
+    async fn pre_generate_calendar(
+        &self,
+        ctx: &RequestContext<'_>,
+        semester: Option<&Semester>,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        let Some(semester) = semester else {
+            return courses;
+        };
+        let (school, options) = (ctx.school, ctx.options);
+        let timezone = school.timezone();
+
+        // The last week with anything in it, or the end of semester
+        let last_week = courses
+            .iter()
+            .flat_map(|course| &course.time)
+            .map(|(start, _)| start.with_timezone(&timezone.offset()).date_naive())
+            .chain(semester.end)
+            .filter_map(|date| semester.week_of(date))
+            .max()
+            .unwrap_or_default();
+
+        let mut result: Vec<Course> = if options.week_numbers == WeekNumbers::None {
+            courses
+        } else {
+            courses
+                .into_iter()
+                .flat_map(|course| {
+                    if course.all_day {
+                        vec![course]
+                    } else {
+                        Self::split_by_week(course, semester, school, options.week_numbers)
+                    }
+                })
+                .collect()
+        };
+
+        if options.week_events {
+            // Weeks already marked, e.g. the first one by the school calendar plugin
+            let marked: HashSet<String> = result.iter().map(|course| course.id.clone()).collect();
+
+            for week in 1..=last_week {
+                let id = format!("校历/第{week}周");
+                if marked.contains(&id) {
+                    continue;
+                }
+                let monday = semester.monday_of(week);
+                result.push(Course::all_day(
+                    &semester.id,
+                    id,
+                    format!("第{week}周"),
+                    CourseKind::Custom("校历".to_string()),
+                    (monday, monday),
+                    &timezone,
+                ));
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::{
+        course::tests::class, timezone::SchoolTimeZone, traits::tests::TestSchool,
+    };
+    use crate::server::options::CalendarOptions;
+    use chrono::NaiveDate;
+
+    fn semester(start: NaiveDate) -> Semester {
+        Semester {
+            id: "2025-2026-1".to_string(),
+            start,
+            end: None,
+        }
+    }
+
+    async fn run(
+        options: CalendarOptions,
+        semester: &Semester,
+        courses: Vec<Course>,
+    ) -> Vec<Course> {
+        let ctx = RequestContext {
+            school: &TestSchool,
+            key: "key",
+            options: &options,
+        };
+        WeekNumberPlugin
+            .pre_generate_calendar(&ctx, Some(semester), courses)
+            .await
+    }
+
+    #[tokio::test]
+    async fn week_in_title_or_notes() {
+        let semester = semester(NaiveDate::from_ymd_opt(2025, 9, 1).unwrap());
+        let tuesday = class("高等数学01班", 1..=3, 1);
+
+        let options = CalendarOptions {
+            week_numbers: WeekNumbers::Title,
+            ..Default::default()
+        };
+        let courses = run(options, &semester, vec![tuesday.clone()]).await;
+        let weeks: Vec<_> = courses
+            .iter()
+            .map(|course| (course.id.as_str(), course.name.as_str(), course.time.len()))
+            .collect();
+        assert_eq!(
+            weeks,
+            vec![
+                ("高等数学01班/第1周", "[第1周] 高等数学", 1),
+                ("高等数学01班/第2周", "[第2周] 高等数学", 1),
+                ("高等数学01班/第3周", "[第3周] 高等数学", 1),
+            ]
+        );
+
+        let options = CalendarOptions {
+            week_numbers: WeekNumbers::Notes,
+            ..Default::default()
+        };
+        let courses = run(options, &semester, vec![tuesday]).await;
+        assert_eq!(courses.len(), 3);
+        assert_eq!(courses[1].name, "高等数学");
+        assert_eq!(courses[1].notes, vec!["第2周"]);
+    }
+
+    #[tokio::test]
+    async fn before_semester_has_no_week() {
+        // The semester starts a week later than the class
+        let semester = semester(NaiveDate::from_ymd_opt(2025, 9, 8).unwrap());
+        let tuesday = class("高等数学01班", 1..=2, 1);
+
+        let options = CalendarOptions {
+            week_numbers: WeekNumbers::Title,
+            ..Default::default()
+        };
+        let courses = run(options, &semester, vec![tuesday.clone()]).await;
+        assert_eq!(courses.len(), 2);
+        assert_eq!(courses[0].name, "[第1周] 高等数学");
+        assert_eq!(courses[0].time, tuesday.time[1..]);
+        assert_eq!(courses[1].id, "高等数学01班");
+        assert_eq!(courses[1].name, "高等数学");
+        assert_eq!(courses[1].time, tuesday.time[..1]);
+    }
+
+    #[tokio::test]
+    async fn week_events_skip_marked_weeks() {
+        let semester = semester(NaiveDate::from_ymd_opt(2025, 9, 1).unwrap());
+        let first_week = Course::all_day(
+            &semester.id,
+            "校历/第1周".to_string(),
+            "第1周".to_string(),
+            CourseKind::Custom("校历".to_string()),
+            (semester.start, semester.start),
+            &SchoolTimeZone::ASIA_SHANGHAI,
+        );
+
+        let options = CalendarOptions {
+            week_events: true,
+            ..Default::default()
+        };
+        let courses = run(
+            options,
+            &semester,
+            vec![class("高等数学01班", 1..=3, 1), first_week],
+        )
+        .await;
+        let ids: Vec<_> = courses.iter().map(|course| course.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec!["高等数学01班", "校历/第1周", "校历/第2周", "校历/第3周"]
+        );
+    }
+}
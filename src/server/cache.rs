@@ -0,0 +1,264 @@
+//! Caches timetables and generated calendars, so that calendar apps polling every few
+//! minutes don't make us log into the school every time.
+//!
+//! The timetable fetched from school is cached once per school adapter and credential key.
+//! Files generated from it are cached per variant: subscription options
+//! (see [`crate::server::options::CalendarOptions::canonical`]) with the file name. A
+//! subscription keeps at most [`MAX_VARIANTS`] of them. Like the credentials, the key is
+//! stored as [`crypto::lookup_key`], so that it can't be used to decrypt them.
+
+use crate::adapters::crypto;
+use crate::adapters::semester::Timetable;
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use sqlx::types::Json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::instrument;
+
+/// Generated files kept for each subscription, the least recently generated are dropped.
+pub const MAX_VARIANTS: i64 = 16;
+
+/// A timetable we have fetched before.
+#[derive(Debug, Clone)]
+pub struct CachedTimetable {
+    /// Timetable fetched from school, after [`crate::plugins::PlugIn::post_fetch`]
+    pub timetable: Timetable,
+    /// When we fetched it from school
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl CachedTimetable {
+    /// Whether this was fetched within `ttl`.
+    pub fn is_fresh(&self, ttl: TimeDelta) -> bool {
+        Utc::now() - self.fetched_at < ttl
+    }
+}
+
+/// A calendar we have generated before.
+#[derive(Debug, Clone)]
+pub struct CachedCalendar {
+    /// The generated file, in the format asked for, see [`crate::server::export::Format`]
+    pub ics: Vec<u8>,
+    /// Value of the `ETag` header, with quotes
+    pub etag: String,
+    /// When the ICS file last changed
+    pub last_modified: DateTime<Utc>,
+    /// When the timetable this is generated from was fetched from school
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl CachedCalendar {
+    /// The `ETag` of an ICS file, with quotes
+    pub fn etag_of(ics: &[u8]) -> String {
+        format!("\"{:x}\"", Sha256::digest(ics))
+    }
+
+    /// Whether the `If-None-Match` header value matches our ETag.
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == self.etag)
+    }
+
+    /// Whether the file changed after the `If-Modified-Since` header value.
+    ///
+    /// Dates we can't parse are taken as long ago, so the file is sent.
+    pub fn modified_since(&self, if_modified_since: &str) -> bool {
+        match DateTime::parse_from_rfc2822(if_modified_since) {
+            // HTTP dates don't have fractions of seconds
+            Ok(since) => self.last_modified.timestamp() > since.timestamp(),
+            Err(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarCache {
+    db: Arc<Mutex<SqlitePool>>,
+    ttl: TimeDelta,
+}
+
+impl CalendarCache {
+    pub fn new(db: Arc<Mutex<SqlitePool>>, ttl: TimeDelta) -> Self {
+        Self { db, ttl }
+    }
+
+    /// How long a cached timetable is used without fetching from school again.
+    pub fn ttl(&self) -> TimeDelta {
+        self.ttl
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn timetable(&self, adapter: &str, key: &str) -> Result<Option<CachedTimetable>> {
+        let database = self.db.lock().await;
+
+        let row = sqlx::query_as::<_, (Json<Timetable>, DateTime<Utc>)>(
+            "SELECT timetable, fetched_at FROM timetable_cache WHERE adapter = ? AND key = ?",
+        )
+        .bind(adapter)
+        .bind(crypto::lookup_key(key))
+        .fetch_optional(&*database)
+        .await?;
+
+        Ok(row.map(|(Json(timetable), fetched_at)| CachedTimetable {
+            timetable,
+            fetched_at,
+        }))
+    }
+
+    /// Store a timetable just fetched from school.
+    #[instrument(skip(self, timetable), err)]
+    pub async fn put_timetable(
+        &self,
+        adapter: &str,
+        key: &str,
+        timetable: Timetable,
+    ) -> Result<CachedTimetable> {
+        let fetched_at = Utc::now();
+
+        let database = self.db.lock().await;
+        sqlx::query(
+            "INSERT OR REPLACE INTO timetable_cache (adapter, key, timetable, fetched_at)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(adapter)
+        .bind(crypto::lookup_key(key))
+        .bind(Json(&timetable))
+        .bind(fetched_at)
+        .execute(&*database)
+        .await?;
+
+        Ok(CachedTimetable {
+            timetable,
+            fetched_at,
+        })
+    }
+
+    #[instrument(skip(self), err)]
+    pub async fn calendar(
+        &self,
+        adapter: &str,
+        key: &str,
+        options: &str,
+    ) -> Result<Option<CachedCalendar>> {
+        let database = self.db.lock().await;
+
+        let row = sqlx::query_as::<_, (Vec<u8>, String, DateTime<Utc>, DateTime<Utc>)>(
+            "SELECT ics, etag, last_modified, fetched_at FROM calendar_cache
+            WHERE adapter = ? AND key = ? AND options = ?",
+        )
+        .bind(adapter)
+        .bind(crypto::lookup_key(key))
+        .bind(options)
+        .fetch_optional(&*database)
+        .await?;
+
+        Ok(
+            row.map(|(ics, etag, last_modified, fetched_at)| CachedCalendar {
+                ics,
+                etag,
+                last_modified,
+                fetched_at,
+            }),
+        )
+    }
+
+    /// Store a newly generated calendar, from the timetable fetched from school at `fetched_at`.
+    ///
+    /// `last_modified` is only updated when the ICS file actually changed.
+    #[instrument(skip(self, ics), err)]
+    pub async fn put_calendar(
+        &self,
+        adapter: &str,
+        key: &str,
+        options: &str,
+        ics: Vec<u8>,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<CachedCalendar> {
+        let now = Utc::now();
+        let etag = CachedCalendar::etag_of(&ics);
+        let last_modified = match self.calendar(adapter, key, options).await? {
+            Some(previous) if previous.etag == etag => previous.last_modified,
+            _ => now,
+        };
+
+        let lookup_key = crypto::lookup_key(key);
+        let database = self.db.lock().await;
+        let mut tx = database.begin().await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO calendar_cache
+            (adapter, key, options, ics, etag, last_modified, fetched_at, rendered_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(adapter)
+        .bind(&lookup_key)
+        .bind(options)
+        .bind(&ics)
+        .bind(&etag)
+        .bind(last_modified)
+        .bind(fetched_at)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        // Every combination of options is a new variant, don't let them pile up
+        sqlx::query(
+            "DELETE FROM calendar_cache WHERE adapter = $1 AND key = $2 AND options NOT IN (
+                SELECT options FROM calendar_cache WHERE adapter = $1 AND key = $2
+                ORDER BY rendered_at DESC LIMIT $3
+            )",
+        )
+        .bind(adapter)
+        .bind(&lookup_key)
+        .bind(MAX_VARIANTS)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(CachedCalendar {
+            ics,
+            etag,
+            last_modified,
+            fetched_at,
+        })
+    }
+
+    /// Forget the timetable and calendars of a subscription, with all options.
+    #[instrument(skip(self), err)]
+    pub async fn remove(&self, adapter: &str, key: &str) -> Result<()> {
+        let database = self.db.lock().await;
+        sqlx::query("DELETE FROM timetable_cache WHERE adapter = ? AND key = ?")
+            .bind(adapter)
+            .bind(crypto::lookup_key(key))
+            .execute(&*database)
+            .await?;
+        sqlx::query("DELETE FROM calendar_cache WHERE adapter = ? AND key = ?")
+            .bind(adapter)
+            .bind(crypto::lookup_key(key))
+            .execute(&*database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forget timetables not fetched, and calendars not generated, since `before`.
+    ///
+    /// Returns how many are deleted.
+    #[instrument(skip(self), err)]
+    pub async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        let database = self.db.lock().await;
+        let timetables = sqlx::query("DELETE FROM timetable_cache WHERE fetched_at < ?")
+            .bind(before)
+            .execute(&*database)
+            .await?;
+        let calendars = sqlx::query("DELETE FROM calendar_cache WHERE rendered_at < ?")
+            .bind(before)
+            .execute(&*database)
+            .await?;
+
+        Ok(timetables.rows_affected() + calendars.rows_affected())
+    }
+}
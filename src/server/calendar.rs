@@ -1,24 +1,45 @@
 use super::state::ServerState;
 use crate::adapters::course::Course;
-use crate::adapters::traits::School;
-use crate::plugins::PlugIn;
+use crate::adapters::semester::Timetable;
+use crate::adapters::traits::{CredentialExpired, School};
+use crate::plugins::{Pipeline, PlugIn, RequestContext};
+use crate::server::dialect::Dialect;
+use crate::server::export::{self, Format};
+use crate::server::options::CalendarOptions;
+use crate::server::revisions::Revision;
 use anyhow::Context;
 use anyhow::Result;
-use axum::extract::State;
-use axum::http::{HeaderMap, HeaderValue, header};
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::response::IntoResponse;
+use chrono::{DateTime, Utc};
 use dioxus::fullstack::FromResponse;
 use dioxus::prelude::*;
-use ics::{ICalendar, Standard, TimeZone};
+use ics::parameters::Value;
+use ics::properties::{Description, DtEnd, DtStart, Summary, Transp, URL};
+use ics::{Event, ICalendar};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{Instrument, Level, event, info_span, instrument};
+use uuid::Uuid;
 
-struct CalendarRet(HeaderMap, Vec<u8>);
+struct CalendarRet(StatusCode, HeaderMap, Vec<u8>);
 
-#[get("/calendar/{school_adapter}/{key}/schedule.ics", state: State<ServerState>)]
+/// `file` is `schedule.ics`, or another [`Format`] of it.
+#[get("/calendar/{school_adapter}/{key}/{file}", state: State<ServerState>, request_headers: HeaderMap, query: Query<Vec<(String, String)>>)]
 #[instrument(err)]
-pub async fn get_calendar_file(school_adapter: String, key: String) -> Result<CalendarRet> {
-    event!(Level::INFO, "Getting credentials from school");
+pub async fn get_calendar_file(
+    school_adapter: String,
+    key: String,
+    file: String,
+) -> Result<CalendarRet> {
+    let format = Format::from_file_name(&file).with_context(|| {
+        format!(
+            "No such file {file}, expected one of {:?}",
+            Format::FILE_NAMES
+        )
+    })?;
     let school: Arc<dyn School> = state
         .school_adapters
         .lock()
@@ -26,8 +47,168 @@ pub async fn get_calendar_file(school_adapter: String, key: String) -> Result<Ca
         .get(&school_adapter.as_str())
         .context("No such school adapter")?
         .clone();
+
+    let mut options = CalendarOptions::from_query(&query)?;
+    // Then the response depends on the User-Agent, which caches should know
+    let guess_dialect = options.client.is_none() && format == Format::Ics;
+    options.client.get_or_insert_with(|| {
+        if format != Format::Ics {
+            // Programs read these, not calendar apps
+            return Dialect::Generic;
+        }
+        let user_agent = request_headers
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .unwrap_or_default();
+        Dialect::from_user_agent(user_agent)
+    });
+    state.plugins.warn_unused_options(&*school, &options);
+    let variant = format!("{}&file={file}", options.canonical());
+    let ctx = RequestContext {
+        school: &*school,
+        key: &key,
+        options: &options,
+    };
+
+    // Credentials are only looked up when going to school. Keys created through another
+    // adapter have nothing cached under this one, and revoking removes cached calendars.
+    let adapter = school.adapter_name();
+    let cached = state.cache.timetable(adapter, &key).await?;
+    let calendar = match cached {
+        Some(cached) if cached.is_fresh(state.cache.ttl()) => {
+            match state.cache.calendar(adapter, &key, &variant).await? {
+                // Generated from this very timetable
+                Some(calendar) if calendar.fetched_at == cached.fetched_at => {
+                    event!(Level::INFO, "Serving cached calendar file");
+                    calendar
+                }
+                _ => {
+                    event!(
+                        Level::INFO,
+                        "Generating calendar file from cached timetable"
+                    );
+                    let ics =
+                        render_calendar(&state, &ctx, &variant, format, cached.timetable, vec![])
+                            .await?;
+                    state
+                        .cache
+                        .put_calendar(adapter, &key, &variant, ics, cached.fetched_at)
+                        .await?
+                }
+            }
+        }
+        cached => match fetch_courses(&*school, &key).await {
+            Ok(timetable) => {
+                let timetable = state
+                    .plugins
+                    .post_fetch(&ctx, timetable)
+                    .instrument(info_span!("Running plugins after fetch"))
+                    .await;
+                let fetched = state.cache.put_timetable(adapter, &key, timetable).await?;
+                let ics =
+                    render_calendar(&state, &ctx, &variant, format, fetched.timetable, vec![])
+                        .await?;
+                state
+                    .cache
+                    .put_calendar(adapter, &key, &variant, ics, fetched.fetched_at)
+                    .await?
+            }
+            Err(error) if error.is::<CredentialExpired>() => {
+                event!(
+                    Level::WARN,
+                    "Credential expired, asking user to login again"
+                );
+                school.mark_cred_stale(&key).await?;
+
+                // The reminder goes along with the last good courses, if we have them
+                let fetched_at = cached
+                    .as_ref()
+                    .map_or_else(Utc::now, |cached| cached.fetched_at);
+                let timetable = cached.map(|cached| cached.timetable).unwrap_or_default();
+                let notice = relogin_notice(&state);
+                let ics = render_calendar(&state, &ctx, &variant, format, timetable, vec![notice])
+                    .await?;
+                // Stored so that Last-Modified only changes when the file does
+                state
+                    .cache
+                    .put_calendar(adapter, &key, &variant, ics, fetched_at)
+                    .await?
+            }
+            Err(error) => {
+                // Serve the last good courses when school API is down, or we fail to read
+                // the credential, so that students never get an empty calendar.
+                let Some(cached) = cached else {
+                    return Err(error);
+                };
+                event!(
+                    Level::WARN,
+                    error = format!("{error:?}"),
+                    "Failed to fetch courses, serving the last good ones"
+                );
+
+                let notice = update_failed_notice(&state, &*school, cached.fetched_at);
+                let ics = render_calendar(
+                    &state,
+                    &ctx,
+                    &variant,
+                    format,
+                    cached.timetable,
+                    vec![notice],
+                )
+                .await?;
+                // Stored with the time of the last good fetch, so that we still try school
+                // on the next request, and Last-Modified only changes when the file does
+                state
+                    .cache
+                    .put_calendar(adapter, &key, &variant, ics, cached.fetched_at)
+                    .await?
+            }
+        },
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, HeaderValue::from_str(&calendar.etag)?);
+    if guess_dialect {
+        headers.insert(header::VARY, HeaderValue::from_static("User-Agent"));
+    }
+    headers.insert(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(
+            &calendar
+                .last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        )?,
+    );
+
+    // If-None-Match wins when both are given (RFC 9110 13.2.2), and headers we can't read
+    // are taken as no match
+    let not_modified = match request_headers.get(header::IF_NONE_MATCH) {
+        Some(if_none_match) => if_none_match
+            .to_str()
+            .is_ok_and(|if_none_match| calendar.matches(if_none_match)),
+        None => request_headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|since| since.to_str().ok())
+            .is_some_and(|since| !calendar.modified_since(since)),
+    };
+    if not_modified {
+        return Ok(CalendarRet(StatusCode::NOT_MODIFIED, headers, vec![]));
+    }
+
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(format.content_type())?,
+    );
+
+    Ok(CalendarRet(StatusCode::OK, headers, calendar.ics))
+}
+
+/// Log in with the credential of `key` and fetch courses from school.
+async fn fetch_courses(school: &dyn School, key: &str) -> Result<Timetable> {
+    event!(Level::INFO, "Getting credentials from database");
     let cred = school
-        .get_cred_from_db(key.as_str())
+        .get_cred_from_db(key)
         .await
         .context("No such key. URL might be wrong.")?;
 
@@ -36,35 +217,148 @@ pub async fn get_calendar_file(school_adapter: String, key: String) -> Result<Ca
         .instrument(info_span!("Getting relevant cookies"))
         .await?;
 
-    let courses = school
+    school
         .courses(&client)
         .instrument(info_span!("Fetching courses"))
-        .await?;
+        .await
+}
 
+/// Run plugins on courses of `timetable` and generate the calendar file in `format`.
+///
+/// `variant` tells apart calendars of the same subscription, as in the cache.
+///
+/// `notices` are shown along with the courses, except in CSV.
+async fn render_calendar(
+    state: &ServerState,
+    ctx: &RequestContext<'_>,
+    variant: &str,
+    format: Format,
+    timetable: Timetable,
+    notices: Vec<Notice>,
+) -> Result<Vec<u8>> {
+    let school = ctx.school;
+    let semester = timetable.semester.as_ref();
     let courses = state
         .plugins
-        .pre_generate_calendar(&*school, courses)
+        .pre_generate_calendar(ctx, semester, timetable.courses)
         .instrument(info_span!("Running plugins"))
         .await;
 
+    match format {
+        Format::Json => return export::to_json(ctx, semester, &courses, &notices),
+        Format::Csv => return export::to_csv(ctx, semester, &courses, &notices),
+        Format::Ics | Format::Jcal => {}
+    }
+
+    let fingerprints: Vec<_> = courses
+        .iter()
+        .flat_map(|course| course.event_fingerprints(school))
+        .collect();
+    let revisions = state
+        .revisions
+        .update(school.adapter_name(), ctx.key, variant, &fingerprints)
+        .instrument(info_span!("Updating event revisions"))
+        .await?;
+
     let calendar_bytes_buf =
         info_span!("Generating calendar file").in_scope(|| -> Result<Vec<_>, anyhow::Error> {
-            let calendar = calendar_from_courses(&*school, &courses)?;
+            let mut calendar = calendar_from_courses(ctx, &state.plugins, &courses, &revisions)?;
+            for notice in &notices {
+                calendar.add_event(notice.to_event(ctx, &state.site_url));
+            }
+            state.plugins.post_render(ctx, &mut calendar);
             let mut calendar_bytes_buf = vec![];
             let writer = std::io::Cursor::new(&mut calendar_bytes_buf);
             calendar.write(writer)?;
 
-            Ok(calendar_bytes_buf)
+            Ok(ctx.options.dialect().finish(calendar_bytes_buf))
         })?;
 
     event!(Level::INFO, "Done generating calendar file");
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str("text/calendar")?,
-    );
+    match format {
+        Format::Jcal => export::to_jcal(&calendar_bytes_buf),
+        _ => Ok(calendar_bytes_buf),
+    }
+}
+
+/// A message to the subscriber, shown along with the courses.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notice {
+    /// Tells apart different notices of the same subscription, e.g. `credential-expired`
+    pub kind: &'static str,
+    pub summary: String,
+    pub description: String,
+}
 
-    Ok(CalendarRet(headers, calendar_bytes_buf))
+/// Telling the user that we failed to update the calendar.
+///
+/// `fetched_at`: when we last fetched courses from school.
+fn update_failed_notice(
+    state: &ServerState,
+    school: &dyn School,
+    fetched_at: DateTime<Utc>,
+) -> Notice {
+    let fetched_at = fetched_at.with_timezone(&school.timezone().offset());
+
+    Notice {
+        kind: "update-failed",
+        summary: "课表更新失败，请重新登录".to_string(),
+        description: format!(
+            "无法从学校服务器获取课表，以下为{}时的课表。\n如果持续失败，请重新登录并订阅：{}",
+            fetched_at.format("%Y-%m-%d %H:%M"),
+            state.site_url
+        ),
+    }
+}
+
+/// Telling the user that the login has expired.
+fn relogin_notice(state: &ServerState) -> Notice {
+    Notice {
+        kind: "credential-expired",
+        summary: "登录已过期，请重新登录".to_string(),
+        description: format!(
+            "学校已不再接受你的登录信息，课表无法继续更新。\n请前往{}重新登录，并用新的链接替换此订阅。",
+            state.site_url
+        ),
+    }
+}
+
+impl Notice {
+    /// An all-day, transparent event today, to show this in the calendar.
+    ///
+    /// The UID only depends on [`Notice::kind`], so that there's only one such event even
+    /// if it's shown for days.
+    fn to_event(&self, ctx: &RequestContext<'_>, url: &str) -> Event<'static> {
+        let school = ctx.school;
+        let dialect = ctx.options.dialect();
+        let today = Utc::now()
+            .with_timezone(&school.timezone().offset())
+            .date_naive();
+
+        let uid = Uuid::new_v5(
+            &Uuid::NAMESPACE_OID,
+            format!("{}/{}/{}", school.adapter_name(), ctx.key, self.kind).as_bytes(),
+        );
+        // Keep DTSTAMP the same within a day, so that the file doesn't change on every request
+        let mut event = Event::new(uid.to_string(), today.format("%Y%m%dT000000Z").to_string());
+        event.push(Summary::new(dialect.escape(&self.summary)));
+        event.push(Description::new(dialect.escape(&self.description)));
+        event.push(URL::new(url.to_string()));
+
+        let mut start = DtStart::new(today.format("%Y%m%d").to_string());
+        start.add(Value::DATE);
+        event.push(start);
+        let mut end = DtEnd::new(
+            (today + chrono::Duration::days(1))
+                .format("%Y%m%d")
+                .to_string(),
+        );
+        end.add(Value::DATE);
+        event.push(end);
+        event.push(Transp::transparent());
+
+        event
+    }
 }
 
 // Deserialize CalendarRet from HTTP response
@@ -78,22 +372,23 @@ impl FromResponse for CalendarRet {
 
 impl IntoResponse for CalendarRet {
     fn into_response(self) -> axum::response::Response {
-        (self.0, self.1).into_response()
+        (self.0, self.1, self.2).into_response()
     }
 }
 
-fn calendar_from_courses<'a, 'b: 'a>(
-    school: &'b dyn School,
+fn calendar_from_courses<'a>(
+    ctx: &RequestContext<'_>,
+    plugins: &Pipeline,
     courses: &[Course],
+    revisions: &HashMap<String, Revision>,
 ) -> Result<ICalendar<'a>> {
     let mut calendar = ICalendar::new("2.0", "南哪另一课表");
 
-    let tzid = "schedule_tz";
-    let tz = TimeZone::standard(tzid, Standard::new("19710101T000000", "+0000", "+0000"));
-    calendar.add_timezone(tz);
+    calendar.add_timezone(ctx.school.timezone().to_vtimezone());
 
     for course in courses {
-        for event in course.to_events(school, tzid)? {
+        for mut event in course.to_events(ctx.school, revisions, ctx.options)? {
+            plugins.on_event(ctx, course, &mut event);
             calendar.add_event(event);
         }
     }
@@ -1,8 +1,10 @@
-use anyhow::Result;
+use crate::plugins::PLUGINS;
+use anyhow::{Context, Result, bail};
 use clap::Parser;
 use serde::Deserialize;
+use std::collections::HashMap;
 use toml;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(author,version,about,long_about=None)]
@@ -12,10 +14,25 @@ struct Args {
     config: String,
 }
 
+/// Unknown keys are rejected, so that misspelled or outdated ones don't go unnoticed.
+/// Keys of older versions are moved to where they are now, see [`migrate_legacy_keys`].
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub db_path: String,
     pub site_url: String,
+    /// How long a generated calendar is served before fetching from school again, in minutes
+    #[serde(default = "default_cache_ttl_minutes")]
+    pub cache_ttl_minutes: i64,
+    /// Login credentials not used for this many days, or rejected by school this many
+    /// days ago, are deleted
+    #[serde(default = "default_credential_ttl_days")]
+    pub credential_ttl_days: i64,
+    /// Buildings to add to those bundled, see [`crate::adapters::buildings`]
+    pub buildings_path: Option<String>,
+    /// Plugins and their config
+    #[serde(default)]
+    pub plugins: PluginsConfig,
     /// OTLP endpoint, e.g. https://otlp-gateway-prod-ap-southeast-1.grafana.net/otlp
     pub otel_endpoint: Option<String>,
     /// Grafana Cloud instance ID (the "Username" shown in the OTLP credentials page)
@@ -24,6 +41,30 @@ pub struct Config {
     pub otel_token: Option<String>,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// Plugins to run, in order, see [`crate::plugins::PLUGINS`]
+    pub order: Vec<String>,
+    /// Plugins that never run for a school adapter, keyed by adapter name
+    pub disabled: HashMap<String, Vec<String>>,
+    /// Config of each plugin, keyed by plugin name.
+    ///
+    /// `enabled = false` makes a plugin only run for subscribers who ask for it.
+    #[serde(flatten)]
+    pub configs: HashMap<String, toml::Table>,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            order: PLUGINS.map(str::to_string).to_vec(),
+            disabled: HashMap::new(),
+            configs: HashMap::new(),
+        }
+    }
+}
+
 const DEFAULT_CFG: &str = r#"
 # The path to SQLite database
 # which stores cookies
@@ -33,15 +74,150 @@ db_path="./cookies.sqlite"
 # No trailing slash
 # Must start with https://
 site_url="https://example.com/sub_dir"
+
+# How long a generated calendar is served
+# before fetching from school again, in minutes
+cache_ttl_minutes=60
+
+# Login credentials not used for this many days,
+# or rejected by school this many days ago,
+# are deleted from database
+credential_ttl_days=90
+
+# Buildings to add to those bundled, for
+# attaching coordinates to course locations.
+# See src/adapters/buildings.toml for its format.
+# buildings_path="./buildings.toml"
+
+# Order to run plugins in. Those not listed
+# run after them, so to turn a plugin off,
+# set enabled=false or use [plugins.disabled].
+[plugins]
+order=["holidays", "school_calendar", "filter", "week_numbers", "alarms"]
+
+# Plugins that never run for some school adapters
+[plugins.disabled]
+# "南京大学研究生" = ["week_numbers"]
+
+# Config of each plugin.
+# With enabled=false, a plugin only runs for
+# subscribers who ask with ?plugin.<name>=true,
+# and subscribers can turn off others with
+# ?plugin.<name>=false.
+[plugins.school_calendar]
+# School calendar (校历) with days the school
+# cancels classes, and exam weeks.
+# See src/plugins/school_calendar.rs for its format.
+# path="./school_calendar.toml"
+
+[plugins.holidays]
+# Where to get holidays: "shuyz", "bundled",
+# or path to a .json or .toml file.
+# When it's unreachable, the last holidays got
+# from it are used, or those bundled.
+source="shuyz"
+# How often to get holidays again, in hours,
+# or 0 to only get them at startup
+refresh_hours=24
+
+# Holidays that replace overlapping ones from source
+# [[plugins.holidays.overrides]]
+# name = "国庆节、中秋节"
+# start = "2025-10-01"
+# end = "2025-10-08"
+# compensate_days = ["2025-09-28", "2025-10-11"]
+
+# Which timetable is followed on compensatory
+# workdays (调休上班), a date or a weekday.
+# Those not given here follow the last weekdays
+# of the holiday, in order.
+[plugins.holidays.compensate_days]
+# 2025-09-28 = "2025-10-07"
+# 2025-10-11 = "周三"
 "#;
 
+fn default_cache_ttl_minutes() -> i64 {
+    60
+}
+
+fn default_credential_ttl_days() -> i64 {
+    90
+}
+
+/// Move keys of older versions in `config` to where they are now, with a warning.
+///
+/// It's an error to give both the old and the new key.
+fn migrate_legacy_keys(config: &mut toml::Table) -> Result<()> {
+    // Before plugins had their own config
+    if let Some(path) = config.remove("school_calendar") {
+        move_key(
+            config,
+            "school_calendar",
+            path,
+            &["plugins", "school_calendar", "path"],
+        )?;
+    }
+    if let Some(days) = config.remove("compensate_days") {
+        move_key(
+            config,
+            "compensate_days",
+            days,
+            &["plugins", "holidays", "compensate_days"],
+        )?;
+    }
+    if let Some(holidays) = config.remove("holidays") {
+        let toml::Value::Table(holidays) = holidays else {
+            bail!("`holidays` in config should be a table, and is now `[plugins.holidays]`");
+        };
+        for (key, value) in holidays {
+            move_key(
+                config,
+                &format!("holidays.{key}"),
+                value,
+                &["plugins", "holidays", &key],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Put `value` of the legacy key `old` at path `new` in `config`.
+fn move_key(config: &mut toml::Table, old: &str, value: toml::Value, new: &[&str]) -> Result<()> {
+    let new_key = new.join(".");
+    let (last, parents) = new.split_last().context("Empty config path")?;
+    let mut table = config;
+    for parent in parents {
+        table = table
+            .entry(*parent)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .with_context(|| format!("`{parent}` in config should be a table"))?;
+    }
+    if table.contains_key(*last) {
+        bail!(
+            "Both `{old}` and `{new_key}` are in config, `{old}` is outdated and should be removed"
+        );
+    }
+
+    warn!("`{old}` in config is outdated, please move it to `{new_key}`");
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
 impl Config {
+    /// Parse the content of `config.toml`.
+    pub fn parse(config: &str) -> Result<Self> {
+        let mut config: toml::Table = toml::from_str(config)?;
+        migrate_legacy_keys(&mut config)?;
+        Ok(config.try_into()?)
+    }
+
     pub fn from_file(path: &str) -> Result<Self> {
         let config = std::fs::read_to_string(path);
 
         match config {
             Ok(config) => {
-                let config: Config = toml::from_str(&config)?;
+                let config = Config::parse(&config)?;
 
                 Ok(config)
             }
@@ -70,3 +246,80 @@ impl Config {
         Self::from_file("./config.toml")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUIRED: &str = r#"
+db_path = "./cookies.sqlite"
+site_url = "https://example.com"
+"#;
+
+    #[test]
+    fn legacy_keys_are_moved() {
+        let config = Config::parse(&format!(
+            r#"{REQUIRED}
+school_calendar = "./school_calendar.toml"
+
+[holidays]
+source = "bundled"
+
+[holidays.compensate_days]
+2025-09-28 = "2025-10-07"
+"#
+        ))
+        .unwrap();
+        let configs = &config.plugins.configs;
+
+        assert_eq!(
+            configs["school_calendar"]["path"].as_str(),
+            Some("./school_calendar.toml")
+        );
+        assert_eq!(configs["holidays"]["source"].as_str(), Some("bundled"));
+        assert_eq!(
+            configs["holidays"]["compensate_days"]["2025-09-28"].as_str(),
+            Some("2025-10-07")
+        );
+    }
+
+    #[test]
+    fn top_level_compensate_days_are_moved() {
+        let config = Config::parse(&format!(
+            r#"{REQUIRED}
+[compensate_days]
+2025-10-11 = "周三"
+"#
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config.plugins.configs["holidays"]["compensate_days"]["2025-10-11"].as_str(),
+            Some("周三")
+        );
+    }
+
+    #[test]
+    fn legacy_and_new_key_together_are_rejected() {
+        let config = Config::parse(&format!(
+            r#"{REQUIRED}
+school_calendar = "./old.toml"
+
+[plugins.school_calendar]
+path = "./new.toml"
+"#
+        ));
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(Config::parse(&format!("{REQUIRED}\ncache_ttl = 60\n")).is_err());
+    }
+
+    #[test]
+    fn default_config_parses() {
+        Config::parse(DEFAULT_CFG).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+//! Calendar apps read ICS files differently, so each gets a dialect it understands.
+//!
+//! The dialect is told by `?client=`, or guessed from the `User-Agent` of the request.
+
+use ics::escape_text;
+
+/// What a calendar app understands in ICS files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Calendar on iOS and macOS
+    Apple,
+    /// Google Calendar
+    Google,
+    /// Outlook and Exchange
+    Outlook,
+    /// ICSx⁵ on Android, which syncs into the system calendar
+    Icsx5,
+    /// Calendar of ColorOS (OPPO, OnePlus)
+    ColorOs,
+    /// Calendar of HarmonyOS (Huawei)
+    HarmonyOs,
+    /// Plain RFC 5545, without vendor properties
+    Generic,
+    /// Everything for everyone, for apps we can't tell
+    Universal,
+}
+
+impl Dialect {
+    /// Names used in `?client=`.
+    pub const NAMES: [&str; 8] = [
+        "apple",
+        "google",
+        "outlook",
+        "icsx5",
+        "coloros",
+        "harmonyos",
+        "generic",
+        "universal",
+    ];
+
+    const ALL: [Self; 8] = [
+        Self::Apple,
+        Self::Google,
+        Self::Outlook,
+        Self::Icsx5,
+        Self::ColorOs,
+        Self::HarmonyOs,
+        Self::Generic,
+        Self::Universal,
+    ];
+
+    /// The name used in `?client=`, one of [`Dialect::NAMES`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Apple => "apple",
+            Self::Google => "google",
+            Self::Outlook => "outlook",
+            Self::Icsx5 => "icsx5",
+            Self::ColorOs => "coloros",
+            Self::HarmonyOs => "harmonyos",
+            Self::Generic => "generic",
+            Self::Universal => "universal",
+        }
+    }
+
+    /// The dialect named `name` in `?client=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|dialect| dialect.name() == name)
+    }
+
+    /// Guess from the `User-Agent` of a calendar request.
+    pub fn from_user_agent(user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| user_agent.contains(p));
+
+        // Outlook for Mac also says Macintosh, so check apps before systems
+        if has(&["google"]) {
+            Self::Google
+        } else if has(&["microsoft", "outlook", "exchange"]) {
+            Self::Outlook
+        } else if has(&["icsx5", "icsdroid"]) {
+            Self::Icsx5
+        } else if has(&["coloros", "oppo", "oneplus", "heytap"]) {
+            Self::ColorOs
+        } else if has(&["harmonyos", "openharmony", "huawei"]) {
+            Self::HarmonyOs
+        } else if has(&[
+            "dataaccessd",
+            "calendaragent",
+            "iphone",
+            "ipad",
+            "macintosh",
+        ]) {
+            Self::Apple
+        } else {
+            Self::Universal
+        }
+    }
+
+    /// Whether to add `X-APPLE-STRUCTURED-LOCATION`, which shows a map in Apple Calendar.
+    pub fn apple_location(&self) -> bool {
+        matches!(self, Self::Apple | Self::Universal)
+    }
+
+    /// Whether to add `EXTENDED-ADDRESS`, which shows a map in ColorOS calendar.
+    pub fn coloros_location(&self) -> bool {
+        matches!(self, Self::ColorOs | Self::Universal)
+    }
+
+    /// Whether to add `X-MICROSOFT-CDO-BUSYSTATUS`, which Outlook prefers over `TRANSP`.
+    pub fn outlook_busy_status(&self) -> bool {
+        matches!(self, Self::Outlook)
+    }
+
+    /// Escape a text value.
+    ///
+    /// RFC 5545 wants `,`, `;` and `\` escaped as well as newlines, but the importers of
+    /// ColorOS and HarmonyOS may show those backslashes as is. They, and apps we can't
+    /// tell, only get newlines escaped.
+    pub fn escape(&self, text: &str) -> String {
+        match self {
+            Self::ColorOs | Self::HarmonyOs | Self::Universal => text.replace('\n', "\\n"),
+            _ => escape_text(text).into_owned(),
+        }
+    }
+
+    /// Whether long lines may be folded as RFC 5545 says.
+    ///
+    /// The importers of ColorOS and HarmonyOS don't always unfold lines, so they get long
+    /// lines as is.
+    pub fn folds_lines(&self) -> bool {
+        !matches!(self, Self::ColorOs | Self::HarmonyOs)
+    }
+
+    /// Adapt a written calendar file to this dialect.
+    pub fn finish(&self, ics: Vec<u8>) -> Vec<u8> {
+        if self.folds_lines() {
+            return ics;
+        }
+
+        // Folding only inserts CRLF and a space, everything else is escaped
+        let mut result = Vec::with_capacity(ics.len());
+        let mut rest = &ics[..];
+        while let Some(fold) = rest.windows(3).position(|w| w == b"\r\n ") {
+            result.extend_from_slice(&rest[..fold]);
+            rest = &rest[fold + 3..];
+        }
+        result.extend_from_slice(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(Dialect::ALL.map(|dialect| dialect.name()), Dialect::NAMES);
+        for dialect in Dialect::ALL {
+            assert_eq!(Dialect::from_name(dialect.name()), Some(dialect));
+        }
+        assert_eq!(Dialect::from_name("thunderbird"), None);
+    }
+
+    #[test]
+    fn user_agents() {
+        for (user_agent, dialect) in [
+            ("iOS/17.5.1 (21F90) dataaccessd/1.0", Dialect::Apple),
+            ("macOS/14.5 (23F79) CalendarAgent/988", Dialect::Apple),
+            ("Google-Calendar-Importer", Dialect::Google),
+            (
+                "Microsoft Office/16.0 (Macintosh; Mac OS X 14.5; Microsoft Outlook 16.86)",
+                Dialect::Outlook,
+            ),
+            (
+                "Microsoft.Exchange/15.20 (Windows NT 6.3; Win64; x64)",
+                Dialect::Outlook,
+            ),
+            (
+                "ICSx5/2.2.3-ose (ical4j/3.2.14 okhttp/4.12.0 Android/14)",
+                Dialect::Icsx5,
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 12; HarmonyOS; NOH-AN00; HMSCore 6.11.0.302) \
+                 AppleWebKit/537.36 (KHTML, like Gecko) Chrome/99.0.4844.88 \
+                 HuaweiBrowser/14.0.0.320 Mobile Safari/537.36",
+                Dialect::HarmonyOs,
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Thunderbird/128.0",
+                Dialect::Universal,
+            ),
+            ("", Dialect::Universal),
+        ] {
+            assert_eq!(
+                Dialect::from_user_agent(user_agent),
+                dialect,
+                "{user_agent}"
+            );
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        let text = "张三, 李四; C:\\课表\n仙Ⅱ-207";
+        for dialect in Dialect::ALL {
+            let expected = match dialect {
+                Dialect::ColorOs | Dialect::HarmonyOs | Dialect::Universal => {
+                    r"张三, 李四; C:\课表\n仙Ⅱ-207"
+                }
+                _ => r"张三\, 李四\; C:\\课表\n仙Ⅱ-207",
+            };
+            assert_eq!(dialect.escape(text), expected, "{dialect:?}");
+        }
+    }
+
+    #[test]
+    fn unfolds_lines() {
+        let summary = "高等数学".repeat(20);
+        let mut event = ics::Event::new("uid", "20250801T000000Z");
+        event.push(ics::properties::Summary::new(summary.clone()));
+        let mut calendar = ics::ICalendar::new("2.0", "test");
+        calendar.add_event(event);
+        let mut ics = vec![];
+        calendar.write(&mut ics).unwrap();
+        let line = format!("SUMMARY:{summary}\r\n");
+
+        for dialect in Dialect::ALL {
+            let finished = String::from_utf8(dialect.finish(ics.clone())).unwrap();
+            assert_eq!(
+                finished.contains(&line),
+                matches!(dialect, Dialect::ColorOs | Dialect::HarmonyOs),
+                "{dialect:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,828 @@
+//! Formats other than ICS, for programs built on the schedule, like dashboards and bots.
+//!
+//! All of them are siblings of `schedule.ics`, take the same query parameters, and are
+//! written from the same courses, after plugins:
+//!
+//! - `schedule.json`: courses with all we know about them, see below.
+//! - `schedule.csv`: one row per occurrence, see below.
+//! - `schedule.jcs`: `schedule.ics` in jCal (RFC 7265). Vendor properties are left out
+//!   unless asked for with `?client=`.
+//!
+//! # `schedule.json`
+//!
+//! The schema only changes in backward compatible ways (adding fields) unless `version`
+//! is increased. Fields not known are `null`.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "school": "南京大学",
+//!   "adapter": "南京大学本科生",
+//!   "timezone": "Asia/Shanghai",
+//!   "semester": { "id": "2025-2026-1", "start": "2025-09-01", "end": "2026-01-18" },
+//!   "courses": [
+//!     {
+//!       "id": "高等数学（一）01班/周1/1-2节",
+//!       "name": "高等数学（一）",
+//!       "kind": "lecture",
+//!       "category": "课程",
+//!       "location": "仙Ⅱ-207",
+//!       "campus": "仙林校区",
+//!       "location_id": "XL0207",
+//!       "geo": { "wgs84": [32.114457, 118.953937], "gcj02": [32.112285, 118.959041] },
+//!       "code": "00000010",
+//!       "class_name": "高等数学（一）01班",
+//!       "teachers": ["张三"],
+//!       "credits": 5.0,
+//!       "attending_classes": [],
+//!       "remarks": null,
+//!       "weeks": [1, 2, 3],
+//!       "periods": [1, 2],
+//!       "all_day": false,
+//!       "notes": [],
+//!       "occurrences": [
+//!         { "start": "2025-09-01T08:00:00+08:00", "end": "2025-09-01T09:50:00+08:00", "week": 1 }
+//!       ]
+//!     }
+//!   ],
+//!   "notices": [
+//!     { "kind": "credential-expired", "summary": "登录已过期，请重新登录", "description": "..." }
+//!   ]
+//! }
+//! ```
+//!
+//! - `kind` is one of `lecture`, `lab`, `exam`, `makeup` and `custom`, and `category` is
+//!   its name shown in calendars.
+//! - `geo` is `[latitude, longitude]` in both WGS-84 and GCJ-02, or `null` with `?geo=false`.
+//! - Times are in the school's time zone. All-day events start at midnight of their first
+//!   day and end at midnight after their last day.
+//! - `week` is the teaching week, if the semester is known.
+//! - `notices` tell the subscriber something is wrong, e.g. the login has expired and the
+//!   courses are from the last time we could fetch them.
+//!
+//! # `schedule.csv`
+//!
+//! UTF-8 with BOM (so that Excel opens it right), quoted as RFC 4180, with a header row:
+//!
+//! `id,name,kind,start,end,all_day,week,location,campus,teachers,notes`
+//!
+//! Fields mean the same as in `schedule.json`. `teachers` are joined with `、`, and
+//! `notes` with newlines.
+//!
+//! Notices come first, as rows with `kind` `notice`, the kind of notice (e.g.
+//! `update-failed`) as `id`, its summary as `name` and its description as `notes`.
+
+use super::calendar::Notice;
+use crate::adapters::course::Course;
+use crate::adapters::geo::Datum;
+use crate::adapters::semester::Semester;
+use crate::plugins::RequestContext;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+/// Formats a subscription can be read in, told by the file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ics,
+    Json,
+    Csv,
+    Jcal,
+}
+
+impl Format {
+    /// File names of formats, in the same order as variants.
+    pub const FILE_NAMES: [&str; 4] = [
+        "schedule.ics",
+        "schedule.json",
+        "schedule.csv",
+        "schedule.jcs",
+    ];
+
+    const ALL: [Self; 4] = [Self::Ics, Self::Json, Self::Csv, Self::Jcal];
+
+    /// The format of `file_name` in subscription links.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        Self::FILE_NAMES
+            .iter()
+            .position(|known| *known == file_name)
+            .map(|index| Self::ALL[index])
+    }
+
+    /// Value of the `Content-Type` header.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Ics => "text/calendar",
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Jcal => "application/calendar+json",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSchedule<'a> {
+    version: u32,
+    school: &'a str,
+    adapter: &'a str,
+    timezone: &'a str,
+    semester: Option<JsonSemester<'a>>,
+    courses: Vec<JsonCourse<'a>>,
+    notices: &'a [Notice],
+}
+
+#[derive(Serialize)]
+struct JsonSemester<'a> {
+    id: &'a str,
+    start: String,
+    end: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonGeo {
+    wgs84: [f64; 2],
+    gcj02: [f64; 2],
+}
+
+#[derive(Serialize)]
+struct JsonCourse<'a> {
+    id: &'a str,
+    name: &'a str,
+    kind: &'static str,
+    category: &'a str,
+    location: Option<&'a str>,
+    campus: Option<&'a str>,
+    location_id: Option<&'a str>,
+    geo: Option<JsonGeo>,
+    code: Option<&'a str>,
+    class_name: Option<&'a str>,
+    teachers: &'a [String],
+    credits: Option<f32>,
+    attending_classes: &'a [String],
+    remarks: Option<&'a str>,
+    weeks: &'a [u32],
+    periods: Option<[u32; 2]>,
+    all_day: bool,
+    notes: &'a [String],
+    occurrences: Vec<JsonOccurrence>,
+}
+
+#[derive(Serialize)]
+struct JsonOccurrence {
+    start: String,
+    end: String,
+    week: Option<u32>,
+}
+
+/// A time in the school's time zone, like `2025-09-01T08:00:00+08:00`.
+fn local_time(ctx: &RequestContext<'_>, time: &DateTime<Utc>) -> String {
+    time.with_timezone(&ctx.school.timezone().offset())
+        .to_rfc3339()
+}
+
+/// The teaching week `time` is in.
+fn week_of(
+    ctx: &RequestContext<'_>,
+    semester: Option<&Semester>,
+    time: &DateTime<Utc>,
+) -> Option<u32> {
+    let date = time
+        .with_timezone(&ctx.school.timezone().offset())
+        .date_naive();
+    semester.and_then(|semester| semester.week_of(date))
+}
+
+/// Write `schedule.json`.
+pub fn to_json(
+    ctx: &RequestContext<'_>,
+    semester: Option<&Semester>,
+    courses: &[Course],
+    notices: &[Notice],
+) -> Result<Vec<u8>> {
+    let courses = courses
+        .iter()
+        .map(|course| JsonCourse {
+            id: &course.id,
+            name: &course.name,
+            kind: course.kind.name(),
+            category: course.kind.category(),
+            location: course.location.as_deref(),
+            campus: course.campus.as_deref(),
+            location_id: course.location_id.as_deref(),
+            geo: course.geo.filter(|_| ctx.options.geo).map(|geo| {
+                let (wgs84, gcj02) = (geo.to(Datum::Wgs84), geo.to(Datum::Gcj02));
+                JsonGeo {
+                    wgs84: [wgs84.latitude(), wgs84.longitude()],
+                    gcj02: [gcj02.latitude(), gcj02.longitude()],
+                }
+            }),
+            code: course.code.as_deref(),
+            class_name: course.class_name.as_deref(),
+            teachers: &course.teachers,
+            credits: course.credits,
+            attending_classes: &course.attending_classes,
+            remarks: course.remarks.as_deref(),
+            weeks: &course.weeks,
+            periods: course.periods.map(|(first, last)| [first, last]),
+            all_day: course.all_day,
+            notes: &course.notes,
+            occurrences: course
+                .time
+                .iter()
+                .map(|(start, end)| JsonOccurrence {
+                    start: local_time(ctx, start),
+                    end: local_time(ctx, end),
+                    week: week_of(ctx, semester, start),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let schedule = JsonSchedule {
+        version: 1,
+        school: ctx.school.school_name(),
+        adapter: ctx.school.adapter_name(),
+        timezone: ctx.school.timezone().tzid,
+        semester: semester.map(|semester| JsonSemester {
+            id: &semester.id,
+            start: semester.start.to_string(),
+            end: semester.end.map(|end| end.to_string()),
+        }),
+        courses,
+        notices,
+    };
+    Ok(serde_json::to_vec_pretty(&schedule)?)
+}
+
+/// Quote a CSV field as RFC 4180, if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A CSV row of `fields`, with line break.
+fn csv_row(fields: &[String]) -> String {
+    let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+    fields.join(",") + "\r\n"
+}
+
+/// Write `schedule.csv`.
+pub fn to_csv(
+    ctx: &RequestContext<'_>,
+    semester: Option<&Semester>,
+    courses: &[Course],
+    notices: &[Notice],
+) -> Result<Vec<u8>> {
+    let mut csv = "\u{feff}id,name,kind,start,end,all_day,week,location,campus,teachers,notes\r\n"
+        .to_string();
+
+    for notice in notices {
+        let mut row = vec![String::new(); 11];
+        row[0] = notice.kind.to_string();
+        row[1] = notice.summary.clone();
+        row[2] = "notice".to_string();
+        row[10] = notice.description.clone();
+        csv += &csv_row(&row);
+    }
+
+    for course in courses {
+        for (start, end) in &course.time {
+            let row = [
+                course.id.clone(),
+                course.name.clone(),
+                course.kind.name().to_string(),
+                local_time(ctx, start),
+                local_time(ctx, end),
+                course.all_day.to_string(),
+                week_of(ctx, semester, start)
+                    .map(|week| week.to_string())
+                    .unwrap_or_default(),
+                course.location.clone().unwrap_or_default(),
+                course.campus.clone().unwrap_or_default(),
+                course.teachers.join("、"),
+                course.notes.join("\n"),
+            ];
+            csv += &csv_row(&row);
+        }
+    }
+
+    Ok(csv.into_bytes())
+}
+
+/// Undo escaping of a text value.
+fn unescape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push('\n'),
+            Some(escaped) => result.push(escaped),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Split a value at `separator`s that are not escaped by `\`.
+fn split_unescaped(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut start, mut escaped) = (0, false);
+    for (index, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == separator => {
+                parts.push(&value[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Split the name and parameters of a content line at `separator`s not in quotes.
+///
+/// Parameter values are quoted when they contain `:`, `;` or `,`, and can't be escaped.
+fn split_unquoted(head: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut start, mut quoted) = (0, false);
+    for (index, c) in head.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            _ if c == separator && !quoted => {
+                parts.push(&head[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&head[start..]);
+    parts
+}
+
+/// `20250901` to `2025-09-01`, and `20250901T080000Z` to `2025-09-01T08:00:00Z`.
+fn jcal_date_time(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 4);
+    for (index, c) in value.chars().enumerate() {
+        if matches!(index, 4 | 6) || (value.contains('T') && matches!(index, 11 | 13)) {
+            result.push(if index < 8 { '-' } else { ':' });
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// The jCal value type of property `name`, when there's no `VALUE` parameter.
+fn jcal_type(name: &str) -> &'static str {
+    match name {
+        "DTSTART" | "DTEND" | "DTSTAMP" | "LAST-MODIFIED" | "CREATED" | "EXDATE" | "RDATE"
+        | "RECURRENCE-ID" => "date-time",
+        "TRIGGER" | "DURATION" => "duration",
+        "SEQUENCE" | "PRIORITY" | "REPEAT" => "integer",
+        "GEO" => "float",
+        "RRULE" => "recur",
+        "URL" => "uri",
+        "TZOFFSETFROM" | "TZOFFSETTO" => "utc-offset",
+        _ if name.starts_with("X-") || name == "EXTENDED-ADDRESS" => "unknown",
+        _ => "text",
+    }
+}
+
+/// Values of a property in jCal.
+fn jcal_values(name: &str, value_type: &str, value: &str) -> Result<Vec<Value>> {
+    Ok(match value_type {
+        "date" | "date-time" => split_unescaped(value, ',')
+            .into_iter()
+            .map(|value| json!(jcal_date_time(value)))
+            .collect(),
+        "integer" => vec![json!(value.parse::<i64>()?)],
+        "float" => {
+            let numbers = split_unescaped(value, ';')
+                .into_iter()
+                .map(str::parse::<f64>)
+                .collect::<Result<Vec<_>, _>>()?;
+            match numbers[..] {
+                [number] => vec![json!(number)],
+                _ => vec![json!(numbers)],
+            }
+        }
+        "utc-offset" if value.len() == 5 => vec![json!(format!("{}:{}", &value[..3], &value[3..]))],
+        "recur" => {
+            let mut recur = Map::new();
+            for part in value.split(';') {
+                let (key, value) = part
+                    .split_once('=')
+                    .with_context(|| format!("Invalid RRULE part {part}"))?;
+                let key = key.to_lowercase();
+                let values: Vec<Value> = value
+                    .split(',')
+                    .map(|value| match key.as_str() {
+                        "until" => json!(jcal_date_time(value)),
+                        "count" | "interval" => {
+                            value.parse::<i64>().map_or(json!(value), |n| json!(n))
+                        }
+                        _ => json!(value),
+                    })
+                    .collect();
+                recur.insert(
+                    key,
+                    match &values[..] {
+                        [value] => value.clone(),
+                        _ => Value::Array(values),
+                    },
+                );
+            }
+            vec![Value::Object(recur)]
+        }
+        "text" if name == "CATEGORIES" => split_unescaped(value, ',')
+            .into_iter()
+            .map(|value| json!(unescape_text(value)))
+            .collect(),
+        "text" => vec![json!(unescape_text(value))],
+        _ => vec![json!(value)],
+    })
+}
+
+/// A content line like `DTSTART;TZID=Asia/Shanghai:20250901T080000` in jCal.
+fn jcal_property(line: &str) -> Result<(String, Value)> {
+    let colon = split_unquoted(line, ':')
+        .first()
+        .map(|head| head.len())
+        .filter(|len| *len < line.len())
+        .with_context(|| format!("Invalid content line {line}"))?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut head = split_unquoted(head, ';').into_iter();
+    let name = head.next().unwrap_or_default().to_uppercase();
+    let mut value_type = jcal_type(&name).to_string();
+    let mut params = Map::new();
+    for param in head {
+        let (key, param_value) = param
+            .split_once('=')
+            .with_context(|| format!("Invalid parameter {param} in {line}"))?;
+        let param_value = param_value.trim_matches('"');
+        if key.eq_ignore_ascii_case("VALUE") {
+            value_type = param_value.to_lowercase();
+        } else {
+            params.insert(key.to_lowercase(), json!(param_value));
+        }
+    }
+
+    let mut property = vec![
+        json!(name.to_lowercase()),
+        Value::Object(params),
+        json!(value_type),
+    ];
+    property.extend(jcal_values(&name, &value_type, value)?);
+    Ok((name, Value::Array(property)))
+}
+
+/// Convert an ICS file to jCal (RFC 7265).
+pub fn to_jcal(ics: &[u8]) -> Result<Vec<u8>> {
+    let ics = std::str::from_utf8(ics)?.replace("\r\n ", "");
+
+    // Components being read, as (name, properties, subcomponents)
+    let mut stack: Vec<(String, Vec<Value>, Vec<Value>)> = vec![];
+    let mut calendar = None;
+    for line in ics.split("\r\n").filter(|line| !line.is_empty()) {
+        let (name, property) = jcal_property(line)?;
+        match name.as_str() {
+            "BEGIN" => stack.push((
+                property[3].as_str().unwrap_or_default().to_lowercase(),
+                vec![],
+                vec![],
+            )),
+            "END" => {
+                let (name, properties, components) =
+                    stack.pop().with_context(|| format!("Unmatched {line}"))?;
+                let component = json!([name, properties, components]);
+                match stack.last_mut() {
+                    Some((_, _, parent)) => parent.push(component),
+                    None => calendar = Some(component),
+                }
+            }
+            _ => match stack.last_mut() {
+                Some((_, properties, _)) => properties.push(property),
+                None => bail!("Property outside of components: {line}"),
+            },
+        }
+    }
+
+    Ok(serde_json::to_vec(
+        &calendar.context("No calendar in ICS file")?,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::course::tests::class;
+    use crate::adapters::geo::GeoLocation;
+    use crate::adapters::timezone::SchoolTimeZone;
+    use crate::adapters::traits::tests::TestSchool;
+    use crate::server::options::CalendarOptions;
+    use chrono::NaiveDate;
+    use ics::parameters::{TzIDParam, Value as ValueParam};
+    use ics::properties::{DtStart, ExDate, Geo, RRule, Summary};
+    use ics::{Event, ICalendar, escape_text};
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(csv_field("仙Ⅱ-207"), "仙Ⅱ-207");
+        assert_eq!(csv_field("张三,李四"), "\"张三,李四\"");
+        assert_eq!(csv_field("a \"b\""), "\"a \"\"b\"\"\"");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+        assert_eq!(csv_row(&["a".into(), "b,c".into()]), "a,\"b,c\"\r\n");
+    }
+
+    #[test]
+    fn jcal_date_times() {
+        assert_eq!(jcal_date_time("20250901"), "2025-09-01");
+        assert_eq!(jcal_date_time("20250901T080000"), "2025-09-01T08:00:00");
+        assert_eq!(jcal_date_time("20250901T080000Z"), "2025-09-01T08:00:00Z");
+    }
+
+    /// Write `calendar` as ICS and convert it to jCal.
+    fn jcal_of(calendar: ICalendar<'_>) -> Value {
+        let mut ics = vec![];
+        calendar.write(&mut ics).unwrap();
+        serde_json::from_slice(&to_jcal(&ics).unwrap()).unwrap()
+    }
+
+    /// The property `name` of the first event in `jcal`.
+    fn event_property<'a>(jcal: &'a Value, name: &str) -> &'a Value {
+        let event = jcal[2]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|component| component[0] == "vevent")
+            .unwrap();
+        event[1]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|property| property[0] == name)
+            .unwrap_or_else(|| panic!("No {name} in {event}"))
+    }
+
+    #[test]
+    fn jcal_round_trip() {
+        let mut event = Event::new("uid", "20250801T000000Z");
+        let mut start = DtStart::new("20250901T080000");
+        start.add(TzIDParam::new("Asia/Shanghai"));
+        event.push(start);
+        event.push(RRule::new("FREQ=WEEKLY;INTERVAL=2;COUNT=8;BYDAY=MO,WE"));
+        let mut exdate = ExDate::new("20250915T080000,20250929T080000");
+        exdate.add(TzIDParam::new("Asia/Shanghai"));
+        event.push(exdate);
+        event.push(Geo::new("32.114457;118.953937"));
+        event.push(Summary::new(escape_text("高数, \"习题课\"; 第1\\2节\n仙Ⅱ")));
+
+        let mut holiday = Event::new("holiday", "20250801T000000Z");
+        let mut start = DtStart::new("20251001");
+        start.add(ValueParam::DATE);
+        holiday.push(start);
+
+        let mut calendar = ICalendar::new("2.0", "test");
+        calendar.add_timezone(SchoolTimeZone::ASIA_SHANGHAI.to_vtimezone());
+        calendar.add_event(event);
+        calendar.add_event(holiday);
+        let jcal = jcal_of(calendar);
+
+        assert_eq!(jcal[0], "vcalendar");
+        assert_eq!(
+            event_property(&jcal, "dtstart"),
+            &json!(["dtstart", {"tzid": "Asia/Shanghai"}, "date-time", "2025-09-01T08:00:00"])
+        );
+        assert_eq!(
+            event_property(&jcal, "rrule"),
+            &json!(["rrule", {}, "recur", {
+                "freq": "WEEKLY", "interval": 2, "count": 8, "byday": ["MO", "WE"]
+            }])
+        );
+        assert_eq!(
+            event_property(&jcal, "exdate"),
+            &json!(["exdate", {"tzid": "Asia/Shanghai"}, "date-time",
+                "2025-09-15T08:00:00", "2025-09-29T08:00:00"])
+        );
+        assert_eq!(
+            event_property(&jcal, "geo"),
+            &json!(["geo", {}, "float", [32.114457, 118.953937]])
+        );
+        assert_eq!(
+            event_property(&jcal, "summary"),
+            &json!(["summary", {}, "text", "高数, \"习题课\"; 第1\\2节\n仙Ⅱ"])
+        );
+
+        let holiday = jcal[2]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|component| component[1][0] == json!(["uid", {}, "text", "holiday"]))
+            .unwrap();
+        assert_eq!(holiday[1][2], json!(["dtstart", {}, "date", "2025-10-01"]));
+
+        let timezone = jcal[2]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|component| component[0] == "vtimezone")
+            .unwrap();
+        let standard = &timezone[2][0];
+        assert!(standard[1].as_array().unwrap().contains(&json!([
+            "tzoffsetfrom",
+            {},
+            "utc-offset",
+            "+08:00"
+        ])));
+    }
+
+    #[test]
+    fn jcal_quoted_parameters() {
+        let ics = concat!(
+            "BEGIN:VCALENDAR\r\n",
+            "BEGIN:VEVENT\r\n",
+            "X-APPLE-STRUCTURED-LOCATION;VALUE=URI;X-TITLE=\"仙Ⅱ: 207\":geo:32.11\r\n",
+            " 2285,118.959041\r\n",
+            "LOCATION:\"仙Ⅱ\"-207\\, 仙林校区\r\n",
+            "CATEGORIES:\"课程,实验\r\n",
+            "END:VEVENT\r\n",
+            "END:VCALENDAR\r\n"
+        );
+        let jcal: Value = serde_json::from_slice(&to_jcal(ics.as_bytes()).unwrap()).unwrap();
+
+        assert_eq!(
+            event_property(&jcal, "x-apple-structured-location"),
+            &json!(["x-apple-structured-location", {"x-title": "仙Ⅱ: 207"}, "uri",
+                "geo:32.112285,118.959041"])
+        );
+        assert_eq!(
+            event_property(&jcal, "location"),
+            &json!(["location", {}, "text", "\"仙Ⅱ\"-207, 仙林校区"])
+        );
+        assert_eq!(
+            event_property(&jcal, "categories"),
+            &json!(["categories", {}, "text", "\"课程", "实验"])
+        );
+    }
+
+    /// A class on Monday of the first two weeks, with everything we know about it.
+    fn sample_course() -> Course {
+        let mut course = class("高等数学（一）01班/周1/1-2节", 1..=2, 0);
+        course.name = "高等数学（一）".to_string();
+        course.campus = Some("仙林校区".to_string());
+        course.location_id = Some("XL0207".to_string());
+        course.geo = Some(GeoLocation::new(32.114457, 118.953937, Datum::Wgs84));
+        course.code = Some("00000010".to_string());
+        course.class_name = Some("高等数学（一）01班".to_string());
+        course.teachers = vec!["张三".to_string(), "李四".to_string()];
+        course.credits = Some(5.0);
+        course.weeks = vec![1, 2];
+        course.periods = Some((1, 2));
+        course.notes = vec!["第一次课带教材".to_string(), "答疑在周三".to_string()];
+        course
+    }
+
+    fn sample_notice() -> Notice {
+        Notice {
+            kind: "credential-expired",
+            summary: "登录已过期，请重新登录".to_string(),
+            description: "请重新登录并订阅：https://example.com".to_string(),
+        }
+    }
+
+    fn sample_semester() -> Semester {
+        Semester {
+            id: "2025-2026-1".to_string(),
+            start: NaiveDate::from_ymd_opt(2025, 9, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2026, 1, 18),
+        }
+    }
+
+    #[test]
+    fn json_schema() {
+        let options = CalendarOptions::default();
+        let ctx = RequestContext {
+            school: &TestSchool,
+            key: "key",
+            options: &options,
+        };
+        let json = to_json(
+            &ctx,
+            Some(&sample_semester()),
+            &[sample_course()],
+            &[sample_notice()],
+        )
+        .unwrap();
+        let mut json: Value = serde_json::from_slice(&json).unwrap();
+
+        // GCJ-02 is tested with the conversion
+        let gcj02 = json["courses"][0]["geo"]["gcj02"].take();
+        assert_eq!(gcj02.as_array().unwrap().len(), 2);
+
+        assert_eq!(
+            json,
+            json!({
+                "version": 1,
+                "school": "测试大学",
+                "adapter": "测试",
+                "timezone": "Asia/Shanghai",
+                "semester": { "id": "2025-2026-1", "start": "2025-09-01", "end": "2026-01-18" },
+                "courses": [{
+                    "id": "高等数学（一）01班/周1/1-2节",
+                    "name": "高等数学（一）",
+                    "kind": "lecture",
+                    "category": "课程",
+                    "location": "仙Ⅱ-207",
+                    "campus": "仙林校区",
+                    "location_id": "XL0207",
+                    "geo": { "wgs84": [32.114457, 118.953937], "gcj02": null },
+                    "code": "00000010",
+                    "class_name": "高等数学（一）01班",
+                    "teachers": ["张三", "李四"],
+                    "credits": 5.0,
+                    "attending_classes": [],
+                    "remarks": null,
+                    "weeks": [1, 2],
+                    "periods": [1, 2],
+                    "all_day": false,
+                    "notes": ["第一次课带教材", "答疑在周三"],
+                    "occurrences": [
+                        {
+                            "start": "2025-09-01T08:00:00+08:00",
+                            "end": "2025-09-01T09:50:00+08:00",
+                            "week": 1
+                        },
+                        {
+                            "start": "2025-09-08T08:00:00+08:00",
+                            "end": "2025-09-08T09:50:00+08:00",
+                            "week": 2
+                        }
+                    ]
+                }],
+                "notices": [{
+                    "kind": "credential-expired",
+                    "summary": "登录已过期，请重新登录",
+                    "description": "请重新登录并订阅：https://example.com"
+                }]
+            })
+        );
+
+        // Without the semester, and with `?geo=false`
+        let options = CalendarOptions {
+            geo: false,
+            ..Default::default()
+        };
+        let ctx = RequestContext {
+            options: &options,
+            ..ctx
+        };
+        let json = to_json(&ctx, None, &[sample_course()], &[]).unwrap();
+        let json: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["semester"], Value::Null);
+        assert_eq!(json["courses"][0]["geo"], Value::Null);
+        assert_eq!(json["courses"][0]["occurrences"][0]["week"], Value::Null);
+    }
+
+    #[test]
+    fn csv_layout() {
+        let options = CalendarOptions::default();
+        let ctx = RequestContext {
+            school: &TestSchool,
+            key: "key",
+            options: &options,
+        };
+        let csv = to_csv(
+            &ctx,
+            Some(&sample_semester()),
+            &[sample_course()],
+            &[sample_notice()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            concat!(
+                "\u{feff}id,name,kind,start,end,all_day,week,location,campus,teachers,notes\r\n",
+                "credential-expired,登录已过期，请重新登录,notice,,,,,,,,",
+                "请重新登录并订阅：https://example.com\r\n",
+                "高等数学（一）01班/周1/1-2节,高等数学（一）,lecture,",
+                "2025-09-01T08:00:00+08:00,2025-09-01T09:50:00+08:00,false,1,",
+                "仙Ⅱ-207,仙林校区,张三、李四,\"第一次课带教材\n答疑在周三\"\r\n",
+                "高等数学（一）01班/周1/1-2节,高等数学（一）,lecture,",
+                "2025-09-08T08:00:00+08:00,2025-09-08T09:50:00+08:00,false,2,",
+                "仙Ⅱ-207,仙林校区,张三、李四,\"第一次课带教材\n答疑在周三\"\r\n",
+            )
+        );
+    }
+}
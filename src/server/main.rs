@@ -2,10 +2,11 @@ use super::state::ServerState;
 use crate::adapters::login_process::LoginProcessManagerLayer;
 use crate::gui::app::App;
 use crate::server::config::Config;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::error_handling::HandleErrorLayer;
 use axum::http::StatusCode;
 use axum::{Extension, Json};
+use chrono::TimeDelta;
 use sqlx::migrate::MigrateDatabase;
 use sqlx::{Sqlite, SqlitePool};
 use tower::{BoxError, ServiceBuilder};
@@ -33,11 +34,19 @@ pub fn server_start() -> Result<()> {
             );
 
             if !Sqlite::database_exists(&config.db_path).await? {
-                Sqlite::create_database(&config.db_path).await?;
+                Sqlite::create_database(&config.db_path)
+                    .await
+                    .with_context(|| format!("Failed to create database {}", config.db_path))?;
             }
-            let db = SqlitePool::connect(config.db_path.as_str()).await?;
+            let db = SqlitePool::connect(config.db_path.as_str())
+                .await
+                .with_context(|| format!("Failed to open database {}", config.db_path))?;
 
-            let state = ServerState::from_config(config, db.clone()).await?;
+            let credential_ttl = TimeDelta::days(config.credential_ttl_days);
+            let state = ServerState::from_config(config, db.clone())
+                .await
+                .context("Failed to initialize server")?;
+            super::sweeper::spawn(state.clone(), credential_ttl);
 
             let router = dioxus::server::router(App)
                 .layer(LoginProcessManagerLayer::new())
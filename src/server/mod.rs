@@ -21,3 +21,27 @@ pub mod calendar;
 /// OpenTelemetry 链路追踪初始化
 #[cfg(feature = "server")]
 pub mod telemetry;
+
+/// 记录日历事件的版本，让SEQUENCE只在课程变动时增加
+#[cfg(feature = "server")]
+pub mod revisions;
+
+/// 订阅链接的查询参数，让每个人定制自己的日历
+#[cfg(feature = "server")]
+pub mod options;
+
+/// 不同日历app能理解的ics方言
+#[cfg(feature = "server")]
+pub mod dialect;
+
+/// 除ics外的其他格式：JSON、CSV和jCal
+#[cfg(feature = "server")]
+pub mod export;
+
+/// 缓存生成的日历，避免日历app每次刷新都去登录学校服务器
+#[cfg(feature = "server")]
+pub mod cache;
+
+/// 定期删除长时间没有使用的登录凭据
+#[cfg(feature = "server")]
+pub mod sweeper;
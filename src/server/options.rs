@@ -0,0 +1,331 @@
+//! Options of a subscription, given as query parameters of `schedule.ics`.
+//!
+//! For example, `schedule.ics?exams=only&alarm=15` gives a calendar with only exams,
+//! each with an alarm 15 minutes before it.
+
+use crate::adapters::course::{Course, CourseKind};
+use crate::plugins::PLUGINS;
+use crate::server::dialect::Dialect;
+use anyhow::{Context, Result, bail};
+use chrono::TimeDelta;
+use std::collections::BTreeMap;
+
+/// Which events to include, regarding exams.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExamsFilter {
+    /// Both courses and exams
+    #[default]
+    All,
+    /// Only exams
+    Only,
+    /// Only courses
+    None,
+}
+
+/// How much to put into event descriptions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotesLevel {
+    /// Campus, teachers and everything else we know
+    #[default]
+    Full,
+    /// Only the campus, and notes added by plugins
+    Minimal,
+}
+
+/// Where to show the teaching week of each event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WeekNumbers {
+    /// Nowhere
+    #[default]
+    None,
+    /// Before the title, e.g. `[第3周] 高等数学`
+    Title,
+    /// In the first line of notes
+    Notes,
+}
+
+/// Alarms before events, as lead times.
+///
+/// Each [`CourseKind`] can have its own alarms, e.g. `?alarm.lecture=15&alarm.exam=1d,1h`.
+/// Kinds without their own use `?alarm=`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlarmOptions {
+    /// `?alarm=`
+    pub default: Vec<TimeDelta>,
+    /// `?alarm.<kind>=`, keyed by [`CourseKind::name`]
+    pub per_kind: BTreeMap<&'static str, Vec<TimeDelta>>,
+}
+
+impl AlarmOptions {
+    /// Lead times of alarms for events of `course`.
+    ///
+    /// All-day events only get alarms asked for their own kind, since `?alarm=` is meant
+    /// for classes.
+    pub fn for_course(&self, course: &Course) -> &[TimeDelta] {
+        match self.per_kind.get(course.kind.name()) {
+            Some(leads) => leads,
+            None if course.all_day => &[],
+            None => &self.default,
+        }
+    }
+}
+
+/// Parse comma separated lead times like `15`, `15m`, `1h`, `1h30m` or `1d`.
+/// A number without unit at the end is minutes, so `1h30` is 90 minutes.
+/// An empty string means no alarm.
+fn parse_lead_times(value: &str) -> Result<Vec<TimeDelta>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|lead| !lead.is_empty())
+        .map(parse_lead_time)
+        .collect()
+}
+
+/// Parse one lead time, as `<number><unit>` segments in order.
+fn parse_lead_time(lead: &str) -> Result<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut rest = lead;
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            bail!("Invalid alarm lead time: {lead}, expected a number before each unit");
+        }
+        let number: i64 = rest[..digits]
+            .parse()
+            .with_context(|| format!("Invalid alarm lead time: {lead}"))?;
+        rest = &rest[digits..];
+
+        let mut units = rest.chars();
+        let segment = match units.next() {
+            None | Some('m') => TimeDelta::try_minutes(number),
+            Some('h') => TimeDelta::try_hours(number),
+            Some('d') => TimeDelta::try_days(number),
+            Some(_) => bail!("Invalid alarm lead time: {lead}, expected unit m, h or d"),
+        };
+        rest = units.as_str();
+        total = segment
+            .and_then(|segment| total.checked_add(&segment))
+            .with_context(|| format!("Alarm lead time too long: {lead}"))?;
+    }
+    Ok(total)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarOptions {
+    /// `?exams=all|only|none`
+    pub exams: ExamsFilter,
+    /// `?alarm=15`, `?alarm.exam=1d,1h`: alarms before events
+    pub alarms: AlarmOptions,
+    /// `?notes=full|minimal`
+    pub notes: NotesLevel,
+    /// `?geo=false`: don't attach coordinates to locations
+    pub geo: bool,
+    /// `?exclude=<course name>`: hide a course, can be given multiple times
+    pub exclude: Vec<String>,
+    /// `?week=title|notes|none`: show the teaching week of each event
+    pub week_numbers: WeekNumbers,
+    /// `?week_events=true`: an all-day event on Monday of each week
+    pub week_events: bool,
+    /// `?plugin.<name>=true|false`: run a plugin or not, overriding `config.toml`
+    pub plugins: BTreeMap<&'static str, bool>,
+    /// `?client=apple|google|...`: the calendar app, guessed from `User-Agent` if not given
+    pub client: Option<Dialect>,
+}
+
+impl Default for CalendarOptions {
+    fn default() -> Self {
+        Self {
+            exams: ExamsFilter::All,
+            alarms: AlarmOptions::default(),
+            notes: NotesLevel::Full,
+            geo: true,
+            exclude: vec![],
+            week_numbers: WeekNumbers::None,
+            week_events: false,
+            plugins: BTreeMap::new(),
+            client: None,
+        }
+    }
+}
+
+impl CalendarOptions {
+    /// Parse from query parameters.
+    ///
+    /// Unknown parameters are ignored, since some calendar apps add their own.
+    pub fn from_query(params: &[(String, String)]) -> Result<Self> {
+        let mut options = Self::default();
+
+        for (name, value) in params {
+            match name.as_str() {
+                "exams" => {
+                    options.exams = match value.as_str() {
+                        "all" => ExamsFilter::All,
+                        "only" => ExamsFilter::Only,
+                        "none" => ExamsFilter::None,
+                        _ => {
+                            bail!("Invalid value for `exams`: {value}, expected all, only or none")
+                        }
+                    }
+                }
+                "alarm" => options.alarms.default = parse_lead_times(value)?,
+                "notes" => {
+                    options.notes = match value.as_str() {
+                        "full" => NotesLevel::Full,
+                        "minimal" => NotesLevel::Minimal,
+                        _ => bail!("Invalid value for `notes`: {value}, expected full or minimal"),
+                    }
+                }
+                "geo" => {
+                    options.geo = value
+                        .parse()
+                        .with_context(|| format!("Invalid value for `geo`: {value}"))?
+                }
+                "exclude" => options.exclude.push(value.clone()),
+                "week" => {
+                    options.week_numbers = match value.as_str() {
+                        "title" => WeekNumbers::Title,
+                        "notes" => WeekNumbers::Notes,
+                        "none" => WeekNumbers::None,
+                        _ => {
+                            bail!(
+                                "Invalid value for `week`: {value}, expected title, notes or none"
+                            )
+                        }
+                    }
+                }
+                "client" => {
+                    options.client = Some(Dialect::from_name(value).with_context(|| {
+                        format!(
+                            "Invalid value for `client`: {value}, expected one of {:?}",
+                            Dialect::NAMES
+                        )
+                    })?)
+                }
+                "week_events" => {
+                    options.week_events = value
+                        .parse()
+                        .with_context(|| format!("Invalid value for `week_events`: {value}"))?
+                }
+                _ => {
+                    if let Some(plugin) = name.strip_prefix("plugin.") {
+                        let plugin = PLUGINS
+                            .into_iter()
+                            .find(|known| *known == plugin)
+                            .with_context(|| {
+                                format!("Unknown plugin in `{name}`, expected one of {PLUGINS:?}")
+                            })?;
+                        let run = value
+                            .parse()
+                            .with_context(|| format!("Invalid value for `{name}`: {value}"))?;
+                        options.plugins.insert(plugin, run);
+                    } else if let Some(kind) = name.strip_prefix("alarm.") {
+                        // Links made before kinds were typed call lectures `class`
+                        let kind = if kind == "class" { "lecture" } else { kind };
+                        let kind = CourseKind::NAMES
+                            .into_iter()
+                            .find(|known| *known == kind)
+                            .with_context(|| {
+                                format!(
+                                    "Unknown event kind in `{name}`, expected one of {:?}",
+                                    CourseKind::NAMES
+                                )
+                            })?;
+                        options
+                            .alarms
+                            .per_kind
+                            .insert(kind, parse_lead_times(value)?);
+                    }
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// A string that is the same for equivalent options, used as cache key.
+    pub fn canonical(&self) -> String {
+        let mut exclude = self.exclude.clone();
+        exclude.sort();
+        exclude.dedup();
+
+        format!(
+            "exams={:?}&alarms={:?}&notes={:?}&geo={}&exclude={:?}&week={:?}&week_events={}&plugins={:?}&client={}",
+            self.exams,
+            self.alarms,
+            self.notes,
+            self.geo,
+            exclude,
+            self.week_numbers,
+            self.week_events,
+            self.plugins,
+            self.dialect().name()
+        )
+    }
+
+    /// The dialect to write the calendar in.
+    pub fn dialect(&self) -> Dialect {
+        self.client.unwrap_or(Dialect::Universal)
+    }
+
+    /// Whether `course` should be in the calendar.
+    pub fn includes(&self, course: &Course) -> bool {
+        let kind_included = match self.exams {
+            ExamsFilter::All => true,
+            ExamsFilter::Only => course.kind == CourseKind::Exam,
+            ExamsFilter::None => course.kind != CourseKind::Exam,
+        };
+
+        kind_included && !self.exclude.contains(&course.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lead_times() {
+        let minutes = |minutes: &[i64]| -> Vec<TimeDelta> {
+            minutes.iter().map(|m| TimeDelta::minutes(*m)).collect()
+        };
+
+        assert_eq!(parse_lead_times("15").unwrap(), minutes(&[15]));
+        assert_eq!(parse_lead_times("15m").unwrap(), minutes(&[15]));
+        assert_eq!(parse_lead_times("1h").unwrap(), minutes(&[60]));
+        assert_eq!(parse_lead_times("1d").unwrap(), minutes(&[24 * 60]));
+        assert_eq!(parse_lead_times("1h30m").unwrap(), minutes(&[90]));
+        assert_eq!(parse_lead_times("1h30").unwrap(), minutes(&[90]));
+        assert_eq!(parse_lead_times("1d2h").unwrap(), minutes(&[26 * 60]));
+        assert_eq!(
+            parse_lead_times("1d, 1h,15").unwrap(),
+            minutes(&[24 * 60, 60, 15])
+        );
+        assert_eq!(parse_lead_times("").unwrap(), minutes(&[]));
+    }
+
+    #[test]
+    fn alarm_class_is_lecture() {
+        let query = [("alarm.class".to_string(), "15".to_string())];
+        let options = CalendarOptions::from_query(&query).unwrap();
+        assert_eq!(
+            options.alarms.per_kind.get("lecture"),
+            Some(&vec![TimeDelta::minutes(15)])
+        );
+    }
+
+    #[test]
+    fn invalid_lead_times() {
+        for lead in [
+            "h1",
+            "h",
+            "1x",
+            "1hh",
+            "1h 30m",
+            "-15",
+            "99999999999999999999",
+        ] {
+            assert!(parse_lead_times(lead).is_err(), "{lead} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Keeps `SEQUENCE` and `LAST-MODIFIED` of calendar events stable across fetches.
+//!
+//! Every event has a stable UID (see [`Course::event_uid`]) and a fingerprint
+//! (see [`Course::event_fingerprint`]). We remember the fingerprint we last served
+//! for each UID, and only bump the sequence when the fingerprint changes, i.e. when
+//! the occurrence actually moved.
+//!
+//! Subscribers of the same course get the same UIDs, but their events may differ with
+//! options (e.g. holidays left out or not), so fingerprints are remembered per
+//! subscription and variant, like the cache (see [`crate::server::cache`]).
+//!
+//! [`Course::event_uid`]: crate::adapters::course::Course::event_uid
+//! [`Course::event_fingerprint`]: crate::adapters::course::Course::event_fingerprint
+
+use crate::adapters::crypto;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::instrument;
+
+/// Revision info of one calendar event
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// Value of `SEQUENCE`
+    pub sequence: i64,
+    /// Value of `LAST-MODIFIED` and `DTSTAMP`
+    pub last_modified: DateTime<Utc>,
+}
+
+impl Revision {
+    /// Revision of an event we have never seen before.
+    pub fn initial() -> Self {
+        Self {
+            sequence: 0,
+            last_modified: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventRevisions {
+    db: Arc<Mutex<SqlitePool>>,
+}
+
+impl EventRevisions {
+    pub fn new(db: Arc<Mutex<SqlitePool>>) -> Self {
+        Self { db }
+    }
+
+    /// Record the current fingerprints of a calendar, returning the revision of each event.
+    ///
+    /// `events`: `(uid, fingerprint)` pairs.
+    /// `variant`: tells apart calendars of the same subscription, see [`crate::server::cache`].
+    #[instrument(skip(self, key, events), err)]
+    pub async fn update(
+        &self,
+        adapter: &str,
+        key: &str,
+        variant: &str,
+        events: &[(String, String)],
+    ) -> Result<HashMap<String, Revision>> {
+        let key = crypto::lookup_key(key);
+        let database = self.db.lock().await;
+        let mut tx = database.begin().await?;
+        let now = Utc::now();
+
+        let stored: HashMap<String, (String, i64, DateTime<Utc>)> =
+            sqlx::query_as::<_, (String, String, i64, DateTime<Utc>)>(
+                "SELECT uid, fingerprint, sequence, last_modified FROM event_revision
+                WHERE adapter = ? AND key = ? AND variant = ?",
+            )
+            .bind(adapter)
+            .bind(&key)
+            .bind(variant)
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(|(uid, fingerprint, sequence, last_modified)| {
+                (uid, (fingerprint, sequence, last_modified))
+            })
+            .collect();
+
+        let mut result = HashMap::new();
+        for (uid, fingerprint) in events {
+            let revision = match stored.get(uid) {
+                Some((stored_fingerprint, sequence, last_modified))
+                    if stored_fingerprint == fingerprint =>
+                {
+                    Revision {
+                        sequence: *sequence,
+                        last_modified: *last_modified,
+                    }
+                }
+                // New or moved, the only ones written
+                stored => {
+                    let revision = Revision {
+                        sequence: stored.map_or(0, |(_, sequence, _)| sequence + 1),
+                        last_modified: now,
+                    };
+                    sqlx::query(
+                        "INSERT OR REPLACE INTO event_revision
+                        (adapter, key, variant, uid, fingerprint, sequence, last_modified, last_seen)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                    )
+                    .bind(adapter)
+                    .bind(&key)
+                    .bind(variant)
+                    .bind(uid)
+                    .bind(fingerprint)
+                    .bind(revision.sequence)
+                    .bind(revision.last_modified)
+                    .bind(now)
+                    .execute(&mut *tx)
+                    .await?;
+                    revision
+                }
+            };
+
+            result.insert(uid.clone(), revision);
+        }
+
+        sqlx::query(
+            "UPDATE event_revision SET last_seen = ? WHERE adapter = ? AND key = ? AND variant = ?",
+        )
+        .bind(now)
+        .bind(adapter)
+        .bind(&key)
+        .bind(variant)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result)
+    }
+
+    /// Forget revisions of a subscription, with all variants.
+    #[instrument(skip(self), err)]
+    pub async fn remove(&self, adapter: &str, key: &str) -> Result<()> {
+        let database = self.db.lock().await;
+        sqlx::query("DELETE FROM event_revision WHERE adapter = ? AND key = ?")
+            .bind(adapter)
+            .bind(crypto::lookup_key(key))
+            .execute(&*database)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forget revisions of calendars not generated since `before`.
+    ///
+    /// Returns how many are deleted.
+    #[instrument(skip(self), err)]
+    pub async fn purge(&self, before: DateTime<Utc>) -> Result<u64> {
+        let database = self.db.lock().await;
+        let deleted = sqlx::query("DELETE FROM event_revision WHERE last_seen < ?")
+            .bind(before)
+            .execute(&*database)
+            .await?;
+
+        Ok(deleted.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::migrations;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn revisions() -> EventRevisions {
+        // Each connection would have its own in-memory database
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        migrations::run(sqlx::migrate!("./migrations/server"), "server", &db)
+            .await
+            .unwrap();
+        EventRevisions::new(Arc::new(Mutex::new(db)))
+    }
+
+    fn events(fingerprints: &[(&str, &str)]) -> Vec<(String, String)> {
+        fingerprints
+            .iter()
+            .map(|(uid, fingerprint)| (uid.to_string(), fingerprint.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn sequence_only_changes_when_events_move() {
+        let revisions = revisions().await;
+        let update = |fingerprints| {
+            let revisions = revisions.clone();
+            async move {
+                revisions
+                    .update("南京大学本科生", "key", "", &events(fingerprints))
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let first = update(&[("a", "monday"), ("b", "tuesday")]).await;
+        assert_eq!(first["a"].sequence, 0);
+
+        let same = update(&[("a", "monday"), ("b", "tuesday")]).await;
+        assert_eq!(same["a"].sequence, 0);
+        assert_eq!(same["a"].last_modified, first["a"].last_modified);
+
+        let moved = update(&[("a", "wednesday"), ("b", "tuesday")]).await;
+        assert_eq!(moved["a"].sequence, 1);
+        assert!(moved["a"].last_modified > first["a"].last_modified);
+        assert_eq!(moved["b"].sequence, 0);
+    }
+
+    #[tokio::test]
+    async fn variants_are_separate() {
+        let revisions = revisions().await;
+        let monday = events(&[("a", "monday")]);
+        let wednesday = events(&[("a", "wednesday")]);
+
+        for _ in 0..2 {
+            for (variant, events) in [("x", &monday), ("y", &wednesday)] {
+                let result = revisions
+                    .update("南京大学本科生", "key", variant, events)
+                    .await
+                    .unwrap();
+                assert_eq!(result["a"].sequence, 0);
+            }
+        }
+    }
+}
@@ -1,10 +1,15 @@
+use crate::adapters::buildings::BuildingRegistry;
+use crate::adapters::migrations;
 use crate::adapters::nju_graduate::NJUGraduateAdapter;
 use crate::adapters::nju_undergrad::NJUUndergradAdaptor;
 use crate::adapters::traits::School;
-use crate::plugins::{PlugIn, get_plugins};
+use crate::plugins::{Pipeline, get_plugins};
+use crate::server::cache::CalendarCache;
 use crate::server::config::Config;
+use crate::server::revisions::EventRevisions;
 use anyhow::Result;
 use axum::extract::FromRef;
+use chrono::TimeDelta;
 use derivative::Derivative;
 use dioxus::fullstack::FullstackContext;
 use dioxus::fullstack::extract::FromRequestParts;
@@ -21,26 +26,38 @@ pub struct ServerState {
     #[derivative(Debug = "ignore")]
     pub school_adapters: Arc<Mutex<HashMap<&'static str, Arc<dyn School>>>>,
     #[derivative(Debug = "ignore")]
-    pub plugins: Arc<Vec<Arc<dyn PlugIn>>>,
+    pub plugins: Arc<Pipeline>,
+    #[derivative(Debug = "ignore")]
+    pub revisions: EventRevisions,
+    #[derivative(Debug = "ignore")]
+    pub cache: CalendarCache,
 }
 
 impl ServerState {
     pub async fn from_config(cfg: Config, db: SqlitePool) -> Result<Self> {
+        migrations::run(sqlx::migrate!("./migrations/server"), "server", &db).await?;
+
         let mut school_adapters = HashMap::<&'static str, Arc<dyn School>>::new();
         let adb = Arc::new(Mutex::new(db.clone()));
+        let buildings = Arc::new(BuildingRegistry::new(cfg.buildings_path.as_deref())?);
         school_adapters.insert(
             "南京大学本科生",
-            Arc::new(NJUUndergradAdaptor::new(adb.clone()).await),
+            Arc::new(NJUUndergradAdaptor::with_buildings(adb.clone(), buildings.clone()).await?),
         );
         school_adapters.insert(
             "南京大学研究生",
-            Arc::new(NJUGraduateAdapter::new(adb.clone()).await),
+            Arc::new(NJUGraduateAdapter::with_buildings(adb.clone(), buildings).await?),
         );
 
+        let adapters: Vec<&str> = school_adapters.keys().copied().collect();
+        let plugins = get_plugins(&cfg.plugins, &adapters, adb.clone()).await?;
+
         Ok(Self {
             site_url: cfg.site_url,
             school_adapters: Arc::new(Mutex::new(school_adapters)),
-            plugins: Arc::new(get_plugins().await?),
+            plugins: Arc::new(plugins),
+            revisions: EventRevisions::new(adb.clone()),
+            cache: CalendarCache::new(adb, TimeDelta::minutes(cfg.cache_ttl_minutes)),
         })
     }
 }
@@ -0,0 +1,62 @@
+//! Periodically deletes login credentials that no calendar app uses anymore, or that
+//! the school no longer accepts.
+//!
+//! A CASTGC cookie is as good as the student's password, so we don't keep it
+//! longer than needed.
+
+use super::state::ServerState;
+use anyhow::Result;
+use chrono::{Local, TimeDelta, Utc};
+use std::time::Duration;
+use tracing::{Level, event, instrument};
+
+/// How often we look for unused credentials
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Start sweeping in background.
+///
+/// Credentials not used for `unused_for` are deleted, together with their cached calendars
+/// and event revisions.
+pub fn spawn(state: ServerState, unused_for: TimeDelta) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(error) = sweep(&state, unused_for).await {
+                event!(
+                    Level::ERROR,
+                    error = format!("{error:?}"),
+                    "Failed to sweep"
+                );
+            }
+        }
+    });
+}
+
+#[instrument(skip(state), err)]
+async fn sweep(state: &ServerState, unused_for: TimeDelta) -> Result<()> {
+    let schools: Vec<_> = state
+        .school_adapters
+        .lock()
+        .await
+        .values()
+        .cloned()
+        .collect();
+
+    let mut purged = 0;
+    let unused_since = Local::now().naive_local() - unused_for;
+    for school in schools {
+        purged += school.purge_unused_creds(unused_since).await?;
+    }
+    let purged_calendars = state.cache.purge(Utc::now() - unused_for).await?;
+    let purged_revisions = state.revisions.purge(Utc::now() - unused_for).await?;
+
+    event!(
+        Level::INFO,
+        purged,
+        purged_calendars,
+        purged_revisions,
+        "Deleted unused credentials"
+    );
+    Ok(())
+}